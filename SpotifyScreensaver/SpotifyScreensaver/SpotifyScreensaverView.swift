@@ -9,7 +9,7 @@ import ScreenSaver
 
 class SpotifyScreensaverView: ScreenSaverView {
     private var squarePosition: CGPoint = .zero
-    private let squareSize = NSSize(width: 250, height: 250)
+    private var squareSize = NSSize(width: 250, height: 250)
     private var squareVelocity: CGVector = .zero
     private let constantVelocity = 5;
     private var cachedImage: NSImage?
@@ -88,6 +88,8 @@ class SpotifyScreensaverView: ScreenSaverView {
     
     struct Image: Codable {
         let url: String
+        let width: Int?
+        let height: Int?
     }
     
     struct Access: Codable {
@@ -99,12 +101,45 @@ class SpotifyScreensaverView: ScreenSaverView {
         let refresh: String
     }
     
+    /// settings.json written by the installer, shared with the Rust renderer
+    struct Settings: Codable {
+        let version: Int?
+        let saver: SaverSettings?
+    }
+    
+    /// Saver settings this view understands, anything missing keeps its default
+    struct SaverSettings: Codable {
+        /// Side of the cover as a fraction of the shorter side of the screen
+        let size: Double?
+        /// Pixels per second the cover moves at
+        let speed: Double?
+        let poll_interval_ms: Int?
+    }
+    
     override init?(frame: NSRect, isPreview: Bool) {
         super.init(frame: frame, isPreview: isPreview)
         squarePosition = CGPoint(x: frame.width / 2, y: frame.height / 2)
         squareVelocity = CGVector(dx: 2 , dy: 2)
         animationTimeInterval = 1.0/60
         let saverBundle = Bundle(for: type(of: self))
+        var pollInterval = 2.0
+        
+        if let url = saverBundle.url(forResource: "settings", withExtension: "json"),
+           let data = try? Data(contentsOf: url, options: .mappedIfSafe),
+           let saver = (try? JSONDecoder().decode(Settings.self, from: data))?.saver {
+            if let size = saver.size {
+                let side = min(frame.width, frame.height) * size
+                squareSize = NSSize(width: side, height: side)
+            }
+            if let speed = saver.speed {
+                // Moves diagonally, spreading the speed over both axes each frame
+                let step = speed * animationTimeInterval / 2.0.squareRoot()
+                squareVelocity = CGVector(dx: step, dy: step)
+            }
+            if let interval = saver.poll_interval_ms {
+                pollInterval = max(Double(interval) / 1000, 0.5)
+            }
+        }
         
         if let url = saverBundle.url(forResource: "constants", withExtension: "json") {
             do{
@@ -129,7 +164,7 @@ class SpotifyScreensaverView: ScreenSaverView {
             } catch {}
         }
         
-        Timer.scheduledTimer(withTimeInterval: 2, repeats: true) { _ in
+        Timer.scheduledTimer(withTimeInterval: pollInterval, repeats: true) { _ in
             Task {
                 await self.loadImage()
             }
@@ -138,15 +173,20 @@ class SpotifyScreensaverView: ScreenSaverView {
     
     override func startAnimation() {
         super.startAnimation()
-        let saverBundle = Bundle(for: type(of: self))
-        if let url = saverBundle.url(forResource: "placeholder", withExtension: "png") {
-            let image = NSImage(byReferencing: url)
+        if let image = placeholderImage() {
             Task {
                 @MainActor in self.cachedImage = image
             }
             Task{ await generateToken()}
         }
     }
+
+    /// The placeholder art bundled with the saver, drawn when an item has no artwork
+    private func placeholderImage() -> NSImage? {
+        let saverBundle = Bundle(for: type(of: self))
+        guard let url = saverBundle.url(forResource: "placeholder", withExtension: "png") else { return nil }
+        return NSImage(byReferencing: url)
+    }
     
     
     private func checkBounds() -> (xAxis: Bool, yAxis: Bool) {
@@ -231,16 +271,23 @@ class SpotifyScreensaverView: ScreenSaverView {
             do {
                 let post = try JSONDecoder().decode(Player.self, from: data) // Since the JSON in the URL
                 let item = post.item
-                let img_url = switch item {
+                let images = switch item {
                 case .TrackObject(let track):
-                    track.album.images[0].url
+                    track.album.images
                 case .EpisodeObject(let episode):
-                    episode.show.images[0].url
+                    episode.show.images
                 case .None:
-                    ""
+                    [Image]()
                 }
                 
-                guard let url = URL(string: img_url) else {return}
+                guard let image = self.bestFit(images: images),
+                      let url = URL(string: image.url) else {
+                    // Without artwork the previous item's cover would stay up
+                    Task {
+                        @MainActor in self.cachedImage = self.placeholderImage()
+                    }
+                    return
+                }
                 URLSession.shared.dataTask(with: url) { data, _, error in
                     if let data = data, let image = NSImage(data: data) {
                         Task {
@@ -253,6 +300,17 @@ class SpotifyScreensaverView: ScreenSaverView {
         task.resume()
     }
     
+    /// Picks the smallest image covering the square on this display, or the largest if none are big enough
+    private func bestFit(images: [Image]) -> Image? {
+        let scale = window?.backingScaleFactor ?? NSScreen.main?.backingScaleFactor ?? 1
+        let side = Int((squareSize.width * scale).rounded(.up))
+        let covering = images.filter { ($0.width ?? 0) >= side }
+        if let smallest = covering.min(by: { ($0.width ?? 0) < ($1.width ?? 0) }) {
+            return smallest
+        }
+        return images.max(by: { ($0.width ?? 0) < ($1.width ?? 0) })
+    }
+    
     private func drawSquare() {
         let squareDrawing = NSRect(x: squarePosition.x - squareSize.width / 2,
                                    y: squarePosition.y - squareSize.height / 2,
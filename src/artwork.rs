@@ -0,0 +1,149 @@
+use bytes::Bytes;
+
+use std::fs::{self, File};
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::playback::Image;
+
+/// Folder artwork is cached in, relative to user.json
+const CACHE_DIR: &str = "artwork";
+/// Default size cap of the cache, 50 MB
+const DEFAULT_MAX_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Represents a file in the cache
+struct Entry {
+    /// File name, derived from the image URL
+    key: String,
+    size: u64,
+}
+
+/// Downloads artwork and keeps it on disk so it can be shown again without a network connection
+///
+/// Entries are evicted least recently used first once the cache grows past its size cap
+pub struct ArtworkCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    client: reqwest::Client,
+    /// Cached files, least recently used first
+    entries: Vec<Entry>,
+}
+
+impl ArtworkCache {
+    /// Opens the cache in the default folder with the default size cap
+    pub fn open_default() -> io::Result<ArtworkCache> {
+        ArtworkCache::open(CACHE_DIR, DEFAULT_MAX_BYTES)
+    }
+
+    /// Opens the cache in `dir`, creating it if needed and indexing the files already inside
+    pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> io::Result<ArtworkCache> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let mut found: Vec<(SystemTime, Entry)> = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            found.push((used, Entry {
+                key: entry.file_name().to_string_lossy().to_string(),
+                size: metadata.len(),
+            }));
+        }
+        // The modified time is bumped on every hit, so it orders entries by last use
+        found.sort_by_key(|(used, _)| *used);
+
+        let mut cache = ArtworkCache {
+            dir,
+            max_bytes,
+            client: reqwest::Client::new(),
+            entries: found.into_iter().map(|(_, entry)| entry).collect(),
+        };
+        cache.evict();
+        Ok(cache)
+    }
+
+    /// Returns the image from `images` best suited to a square of `side` physical pixels
+    ///
+    /// Returns `None` if the list is empty or the image is neither cached nor downloadable
+    pub async fn fetch_best(&mut self, images: &[Image], side: u32) -> Option<Bytes> {
+        let image = Image::best_fit(images, side)?;
+        self.fetch(&image.url).await
+    }
+
+    /// Returns the image at `url`, downloading it only when it is not already cached
+    ///
+    /// `file://` URLs, reported by local players, are read straight from disk
+    pub async fn fetch(&mut self, url: &str) -> Option<Bytes> {
+        if url.starts_with("file://") {
+            // Paths are percent-encoded, e.g. spaces as %20
+            let path = reqwest::Url::parse(url).ok()?.to_file_path().ok()?;
+            return fs::read(path).ok().map(Bytes::from);
+        }
+
+        let key = cache_key(url);
+        let path = self.dir.join(&key);
+
+        if let Some(index) = self.entries.iter().position(|entry| entry.key == key) {
+            match fs::read(&path) {
+                Ok(contents) => {
+                    self.touch(index);
+                    return Some(Bytes::from(contents));
+                }
+                // File was removed from under us, download it again
+                Err(_) => {
+                    self.entries.remove(index);
+                }
+            }
+        }
+
+        let response = self.client.get(url).send().await.ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        let contents = response.bytes().await.ok()?;
+
+        // Failing to cache only costs a download next time
+        if fs::write(&path, &contents).is_ok() {
+            self.entries.push(Entry {
+                key,
+                size: contents.len() as u64,
+            });
+            self.evict();
+        }
+        Some(contents)
+    }
+
+    /// Marks the entry at `index` as most recently used
+    fn touch(&mut self, index: usize) {
+        let entry = self.entries.remove(index);
+        if let Ok(file) = File::options().append(true).open(self.dir.join(&entry.key)) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        self.entries.push(entry);
+    }
+
+    /// Removes least recently used entries until the cache fits its size cap
+    fn evict(&mut self) {
+        let mut total: u64 = self.entries.iter().map(|entry| entry.size).sum();
+        while total > self.max_bytes && !self.entries.is_empty() {
+            let entry = self.entries.remove(0);
+            let _ = fs::remove_file(self.dir.join(&entry.key));
+            total -= entry.size;
+        }
+    }
+}
+
+/// File name for a URL, a 64-bit FNV-1a hash so it stays stable between builds
+fn cache_key(url: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in url.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
@@ -0,0 +1,431 @@
+use iced::{Point, Rectangle, Size, Vector};
+use serde::{ Serialize, Deserialize };
+
+use std::f32::consts::PI;
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::settings::SaverSettings;
+
+const CORNERS_FILE: &str = "corners.json";
+
+/// Longest step simulated at once, so a stalled frame does not teleport the square
+const MAX_STEP: Duration = Duration::from_millis(100);
+/// Random angles stay this far from the axes so the square never crawls along an edge
+const MIN_AXIS_ANGLE: f32 = PI / 9.0;
+
+/// Represents a square bouncing around a rectangle, moved by elapsed time rather than per frame
+pub struct BounceSim {
+    /// Top left corner of the square
+    position: Point,
+    /// Pixels per second
+    velocity: Vector,
+    bounds: Size,
+    /// Side of the square as a fraction of the shorter side of the bounds
+    size_fraction: f32,
+    /// Height of the text moving along with the square, which bounces off the edges too
+    caption: f32,
+    /// How many pixels off the other edge a bounce may be and still count as a corner hit
+    corner_tolerance: f32,
+    /// Corner hit last, so reaching its second edge a moment later is not counted again
+    last_corner: Option<Corner>,
+}
+
+/// Represents a corner of the bounds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    fn new(left: bool, top: bool) -> Corner {
+        match (left, top) {
+            (true, true) => Corner::TopLeft,
+            (false, true) => Corner::TopRight,
+            (true, false) => Corner::BottomLeft,
+            (false, false) => Corner::BottomRight,
+        }
+    }
+
+    /// Where this corner is within `bounds`
+    pub fn point(self, bounds: Size) -> Point {
+        match self {
+            Corner::TopLeft => Point::ORIGIN,
+            Corner::TopRight => Point::new(bounds.width, 0.0),
+            Corner::BottomLeft => Point::new(0.0, bounds.height),
+            Corner::BottomRight => Point::new(bounds.width, bounds.height),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Corner::TopLeft => "top left",
+            Corner::TopRight => "top right",
+            Corner::BottomLeft => "bottom left",
+            Corner::BottomRight => "bottom right",
+        }
+    }
+}
+
+impl BounceSim {
+    /// Places the square inside `bounds` with the speed, size and angle from `settings`
+    ///
+    /// The starting position and any unset angle come from `settings.seed`, or the clock if that is unset too
+    pub fn new(settings: &SaverSettings, bounds: Size) -> BounceSim {
+        let seed = settings.seed.unwrap_or_else(|| {
+            SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_nanos() as u64)
+        });
+        let mut random = SplitMix(seed);
+
+        let angle = match settings.angle {
+            Some(degrees) => degrees.to_radians(),
+            None => {
+                let quadrant = (random.next_f32() * 4.0).floor();
+                quadrant * PI / 2.0 + MIN_AXIS_ANGLE + random.next_f32() * (PI / 2.0 - 2.0 * MIN_AXIS_ANGLE)
+            }
+        };
+        let mut sim = BounceSim {
+            position: Point::ORIGIN,
+            velocity: Vector::new(angle.cos() * settings.speed, angle.sin() * settings.speed),
+            bounds,
+            size_fraction: settings.size.clamp(0.01, 1.0),
+            caption: 0.0,
+            corner_tolerance: settings.corner_tolerance.max(0.0),
+            last_corner: None,
+        };
+        let side = sim.side();
+        sim.position = Point::new(
+            random.next_f32() * (bounds.width - side).max(0.0),
+            random.next_f32() * (bounds.height - side).max(0.0),
+        );
+        sim
+    }
+
+    /// Side of the square in logical pixels
+    pub fn side(&self) -> f32 {
+        self.bounds.width.min(self.bounds.height) * self.size_fraction
+    }
+
+    /// Top left corner of the square and its caption
+    pub fn position(&self) -> Point {
+        self.position
+    }
+
+    /// Area taken by the square and its caption
+    pub fn body(&self) -> Rectangle {
+        let side = self.side();
+        Rectangle::new(self.position, Size::new(side, side + self.caption))
+    }
+
+    pub fn bounds(&self) -> Size {
+        self.bounds
+    }
+
+    /// Changes the area the square bounces in, keeping it inside
+    pub fn resize(&mut self, bounds: Size) {
+        self.bounds = bounds;
+        self.keep_inside();
+    }
+
+    /// Takes the size, speed and corner tolerance from `settings`, keeping the square where it is and its direction
+    pub fn retune(&mut self, settings: &SaverSettings) {
+        let speed = self.velocity.x.hypot(self.velocity.y);
+        if speed > 0.0 {
+            self.velocity = self.velocity * (settings.speed / speed);
+        }
+        self.size_fraction = settings.size.clamp(0.01, 1.0);
+        self.corner_tolerance = settings.corner_tolerance.max(0.0);
+        self.keep_inside();
+    }
+
+    /// Changes the height of the text moving with the square, keeping both inside
+    pub fn set_caption(&mut self, height: f32) {
+        self.caption = height.max(0.0);
+        self.keep_inside();
+    }
+
+    fn keep_inside(&mut self) {
+        let (max_x, max_y) = self.travel();
+        self.position.x = self.position.x.clamp(0.0, max_x);
+        self.position.y = self.position.y.clamp(0.0, max_y);
+    }
+
+    /// Advances the square by `elapsed`, reflecting it off every edge it reaches
+    ///
+    /// Returns the corner hit, if a bounce off one edge landed within the tolerance of the other
+    pub fn step(&mut self, elapsed: Duration) -> Option<Corner> {
+        let seconds = elapsed.min(MAX_STEP).as_secs_f32();
+        let (max_x, max_y) = self.travel();
+        let (x, vx) = reflect(self.position.x + self.velocity.x * seconds, self.velocity.x, max_x);
+        let (y, vy) = reflect(self.position.y + self.velocity.y * seconds, self.velocity.y, max_y);
+        let bounced_x = vx.signum() != self.velocity.x.signum();
+        let bounced_y = vy.signum() != self.velocity.y.signum();
+        self.position = Point::new(x, y);
+        self.velocity = Vector::new(vx, vy);
+
+        // Moving right again means the left edge was hit, and likewise for the top
+        let corner = match (bounced_x, bounced_y) {
+            (false, false) => return None,
+            (true, true) => Some(Corner::new(vx > 0.0, vy > 0.0)),
+            (true, false) => (y.min(max_y - y) <= self.corner_tolerance).then(|| Corner::new(vx > 0.0, y < max_y / 2.0)),
+            (false, true) => (x.min(max_x - x) <= self.corner_tolerance).then(|| Corner::new(x < max_x / 2.0, vy > 0.0)),
+        };
+        let repeated = corner.is_some() && corner == self.last_corner;
+        self.last_corner = corner;
+        corner.filter(|_| !repeated)
+    }
+
+    /// Predicts the next corner hit and how long until it happens, looking at most `horizon` ahead
+    pub fn next_corner(&self, horizon: Duration) -> Option<(Corner, Duration)> {
+        let horizon = horizon.as_secs_f32();
+        let (max_x, max_y) = self.travel();
+        let x = Axis { position: self.position.x, velocity: self.velocity.x, max: max_x };
+        let y = Axis { position: self.position.y, velocity: self.velocity.y, max: max_y };
+        let along_x = x.first_corner(&y, self.corner_tolerance, horizon)
+            .map(|(time, left, top)| (time, Corner::new(left, top)));
+        let along_y = y.first_corner(&x, self.corner_tolerance, horizon)
+            .map(|(time, top, left)| (time, Corner::new(left, top)));
+        [along_x, along_y]
+            .into_iter()
+            .flatten()
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(time, corner)| (corner, Duration::from_secs_f32(time)))
+    }
+
+    /// How far the top left corner can move on each axis
+    fn travel(&self) -> (f32, f32) {
+        let side = self.side();
+        ((self.bounds.width - side).max(0.0), (self.bounds.height - side - self.caption).max(0.0))
+    }
+}
+
+/// Bounces every pair of overlapping squares off each other, each acting as a wall to the other
+pub fn collide_all(sims: &mut [&mut BounceSim]) {
+    for index in 1..sims.len() {
+        let (before, after) = sims.split_at_mut(index);
+        let current = &mut before[index - 1];
+        for other in after.iter_mut() {
+            collide(current, other);
+        }
+    }
+}
+
+fn collide(a: &mut BounceSim, b: &mut BounceSim) {
+    let (a_body, b_body) = (a.body(), b.body());
+    let overlap_x = (a_body.x + a_body.width).min(b_body.x + b_body.width) - a_body.x.max(b_body.x);
+    let overlap_y = (a_body.y + a_body.height).min(b_body.y + b_body.height) - a_body.y.max(b_body.y);
+    if overlap_x <= 0.0 || overlap_y <= 0.0 {
+        return;
+    }
+
+    // The axis they overlap least on is the one they just crossed, so they are pushed apart along it
+    if overlap_x < overlap_y {
+        let direction = if a_body.center_x() < b_body.center_x() { -1.0 } else { 1.0 };
+        a.position.x += direction * overlap_x / 2.0;
+        b.position.x -= direction * overlap_x / 2.0;
+        a.velocity.x = direction * a.velocity.x.abs();
+        b.velocity.x = -direction * b.velocity.x.abs();
+    } else {
+        let direction = if a_body.center_y() < b_body.center_y() { -1.0 } else { 1.0 };
+        a.position.y += direction * overlap_y / 2.0;
+        b.position.y -= direction * overlap_y / 2.0;
+        a.velocity.y = direction * a.velocity.y.abs();
+        b.velocity.y = -direction * b.velocity.y.abs();
+    }
+    a.keep_inside();
+    b.keep_inside();
+}
+
+/// Represents the motion of the square along one axis
+struct Axis {
+    position: f32,
+    velocity: f32,
+    /// Furthest the position can go
+    max: f32,
+}
+
+impl Axis {
+    /// Walks this axis' edge hits, returning the time of the first one where `other` is within `tolerance` of an edge too
+    ///
+    /// Along with the time, whether the edge hit was the low one and whether `other` was near its low edge
+    fn first_corner(&self, other: &Axis, tolerance: f32, horizon: f32) -> Option<(f32, bool, bool)> {
+        if self.velocity == 0.0 || self.max <= 0.0 {
+            return None;
+        }
+        let mut time = if self.velocity > 0.0 { (self.max - self.position) / self.velocity } else { self.position / -self.velocity };
+        let period = self.max / self.velocity.abs();
+        let mut low = self.velocity < 0.0;
+        while time <= horizon {
+            let (other_at, _) = reflect(other.position + other.velocity * time, other.velocity, other.max);
+            if other_at.min(other.max - other_at) <= tolerance {
+                return Some((time, low, other_at < other.max / 2.0));
+            }
+            time += period;
+            low = !low;
+        }
+        None
+    }
+}
+
+/// Folds `position` back into `0..=max` as if it bounced off both ends, flipping `velocity` if it ends up moving back
+fn reflect(position: f32, velocity: f32, max: f32) -> (f32, f32) {
+    if max <= 0.0 {
+        return (0.0, velocity);
+    }
+    let folded = position.rem_euclid(2.0 * max);
+    if folded <= max {
+        (folded, velocity)
+    } else {
+        (2.0 * max - folded, -velocity)
+    }
+}
+
+/// Represents corners.json, counting corner hits across runs
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CornerCounter {
+    pub hits: u64,
+}
+
+impl CornerCounter {
+    /// Reads corners.json, starting from zero if it is missing
+    pub fn load() -> CornerCounter {
+        fs::read_to_string(CORNERS_FILE)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Counts a hit and saves the new total
+    pub fn record(&mut self) {
+        self.hits += 1;
+        if let Err(e) = fs::write(CORNERS_FILE, serde_json::to_string(self).expect("Could not convert")) {
+            println!("Could not write {}: {}", CORNERS_FILE, e);
+        }
+    }
+}
+
+/// Represents a small deterministic random number generator, enough to place the square and its confetti
+pub struct SplitMix(pub u64);
+
+impl SplitMix {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0.0..1.0`
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A square of side 150 in 1000x600 bounds, so its top left corner travels 850 across and 450 down
+    fn sim(position: (f32, f32), velocity: (f32, f32), corner_tolerance: f32) -> BounceSim {
+        BounceSim {
+            position: Point::new(position.0, position.1),
+            velocity: Vector::new(velocity.0, velocity.1),
+            bounds: Size::new(1000.0, 600.0),
+            size_fraction: 0.25,
+            caption: 0.0,
+            corner_tolerance,
+            last_corner: None,
+        }
+    }
+
+    const FRAME: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn reflect_folds_back_across_both_ends() {
+        let cases = [
+            ((50.0, 1.0, 100.0), (50.0, 1.0)),
+            ((100.0, 1.0, 100.0), (100.0, 1.0)),
+            ((120.0, 1.0, 100.0), (80.0, -1.0)),
+            ((-20.0, -1.0, 100.0), (20.0, 1.0)),
+            ((250.0, 1.0, 100.0), (50.0, 1.0)),
+            ((-150.0, -1.0, 100.0), (50.0, -1.0)),
+            ((30.0, 1.0, 0.0), (0.0, 1.0)),
+        ];
+        for ((position, velocity, max), expected) in cases {
+            assert_eq!(reflect(position, velocity, max), expected, "{position} within {max}");
+        }
+    }
+
+    #[test]
+    fn step_flips_velocity_on_one_wall() {
+        let mut square = sim((840.0, 200.0), (200.0, 100.0), 4.0);
+        assert_eq!(square.step(FRAME), None);
+        assert_eq!(square.position, Point::new(840.0, 210.0));
+        assert_eq!(square.velocity, Vector::new(-200.0, 100.0));
+    }
+
+    #[test]
+    fn step_reports_an_exact_corner_hit() {
+        let mut square = sim((840.0, 440.0), (200.0, 200.0), 4.0);
+        assert_eq!(square.step(FRAME), Some(Corner::BottomRight));
+        assert_eq!(square.velocity, Vector::new(-200.0, -200.0));
+    }
+
+    #[test]
+    fn step_reports_a_near_corner_hit_within_the_tolerance() {
+        assert_eq!(sim((840.0, 2.0), (200.0, 0.0), 4.0).step(FRAME), Some(Corner::TopRight));
+        assert_eq!(sim((2.0, 300.0), (0.0, 1000.0), 4.0).step(FRAME), None);
+        assert_eq!(sim((10.0, 440.0), (0.0, 200.0), 4.0).step(FRAME), None);
+        assert_eq!(sim((10.0, 440.0), (0.0, 200.0), 12.0).step(FRAME), Some(Corner::BottomLeft));
+    }
+
+    #[test]
+    fn step_counts_the_second_edge_of_a_corner_once() {
+        let mut square = sim((845.0, 440.0), (100.0, 100.0), 20.0);
+        // The right edge first, with the bottom one 0 pixels away
+        assert_eq!(square.step(FRAME), Some(Corner::BottomRight));
+        let (position, velocity) = (square.position, square.velocity);
+        // Then the bottom edge, 15 pixels off the right one
+        assert_eq!(square.step(FRAME), None);
+        // Which would have counted without the hit just before
+        let mut fresh = sim((position.x, position.y), (velocity.x, velocity.y), 20.0);
+        assert_eq!(fresh.step(FRAME), Some(Corner::BottomRight));
+    }
+
+    #[test]
+    fn step_clamps_a_stalled_frame() {
+        let mut square = sim((100.0, 100.0), (100.0, 0.0), 4.0);
+        square.step(Duration::from_secs(5));
+        assert_eq!(square.position, Point::new(100.0 + 100.0 * MAX_STEP.as_secs_f32(), 100.0));
+    }
+
+    #[test]
+    fn next_corner_agrees_with_stepping() {
+        // About a frame at 60 Hz, the rounding adds up over a few thousand of them
+        let tick = Duration::from_millis(16);
+        let slack = tick * 2;
+        let horizon = Duration::from_secs(120);
+        let mut checked = 0;
+        for seed in 0..20 {
+            let settings = SaverSettings { seed: Some(seed), ..SaverSettings::default() };
+            let mut square = BounceSim::new(&settings, Size::new(1000.0, 600.0));
+            let Some((corner, after)) = square.next_corner(horizon) else { continue };
+            let mut elapsed = Duration::ZERO;
+            let hit = loop {
+                elapsed += tick;
+                if let Some(hit) = square.step(tick) {
+                    break hit;
+                }
+                assert!(elapsed <= after + slack, "seed {seed} passed the predicted {corner:?} hit");
+            };
+            assert_eq!(hit, corner, "seed {seed}");
+            assert!(elapsed.abs_diff(after) <= slack, "seed {seed} hit after {elapsed:?}, predicted {after:?}");
+            checked += 1;
+        }
+        assert!(checked > 0, "No seed hits a corner within {horizon:?}");
+    }
+}
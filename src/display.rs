@@ -0,0 +1,47 @@
+use display_info::DisplayInfo;
+use iced::{Point, Rectangle, Size};
+
+/// Represents a monitor, in logical pixels on the virtual desktop
+#[derive(Clone, Copy, Debug)]
+pub struct Display {
+    pub bounds: Rectangle,
+    pub primary: bool,
+}
+
+impl Display {
+    /// Lists the connected monitors, primary first, empty if they could not be read
+    pub fn all() -> Vec<Display> {
+        let infos = match DisplayInfo::all() {
+            Ok(infos) => infos,
+            Err(e) => {
+                println!("Could not list displays: {e}");
+                return Vec::new();
+            }
+        };
+        let mut displays: Vec<Display> = infos
+            .iter()
+            .map(|info| {
+                // Windows reports physical pixels, the other platforms already report logical ones
+                let scale = if cfg!(target_os = "windows") { info.scale_factor.max(1.0) } else { 1.0 };
+                Display {
+                    bounds: Rectangle::new(
+                        Point::new(info.x as f32 / scale, info.y as f32 / scale),
+                        Size::new(info.width as f32 / scale, info.height as f32 / scale),
+                    ),
+                    primary: info.is_primary,
+                }
+            })
+            .collect();
+        displays.sort_by_key(|display| !display.primary);
+        displays
+    }
+
+    /// The smallest display covering every one of `displays`, `None` if there are none
+    pub fn span(displays: &[Display]) -> Option<Display> {
+        displays
+            .iter()
+            .map(|display| display.bounds)
+            .reduce(|all, bounds| all.union(&bounds))
+            .map(|bounds| Display { bounds, primary: true })
+    }
+}
@@ -0,0 +1,355 @@
+use futures::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use tokio::time::sleep;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::history::HistoryLogger;
+use crate::hooks::Hooks;
+use crate::playback::{Chapter, Device, Episode, PlaybackState, PlayingItem, Track};
+use crate::source::{NowPlayingSource, SourceError};
+
+/// How often sources are polled unless configured otherwise
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(2);
+/// How far the position may drift from the expected one before it counts as a seek
+const SEEK_TOLERANCE_MS: u64 = 3000;
+
+/// Represents a change between two successive player states
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum PlaybackEvent {
+    /// A different track started
+    TrackChanged(Track),
+    /// A different podcast episode started
+    EpisodeChanged(Episode),
+    /// A different audiobook chapter started
+    ChapterChanged(Chapter),
+    Paused,
+    Resumed,
+    /// The position jumped somewhere other than where playback would have reached
+    Seeked { position_ms: u64 },
+    /// Playback moved to another device
+    DeviceChanged(Device),
+    /// Nothing drawable is playing anymore
+    Stopped,
+    /// The saved login was revoked or expired and cannot be refreshed
+    AuthLost,
+}
+
+impl fmt::Display for PlaybackEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlaybackEvent::TrackChanged(track) => write!(f, "track changed: {} - {}", track.name, track.artist_names()),
+            PlaybackEvent::EpisodeChanged(episode) => write!(f, "episode changed: {} - {}", episode.name, episode.show.name),
+            PlaybackEvent::ChapterChanged(chapter) => write!(f, "chapter changed: {} - {}", chapter.name, chapter.audiobook.name),
+            PlaybackEvent::Paused => write!(f, "paused"),
+            PlaybackEvent::Resumed => write!(f, "resumed"),
+            PlaybackEvent::Seeked { position_ms } => write!(f, "seeked to {}s", position_ms / 1000),
+            PlaybackEvent::DeviceChanged(device) => write!(f, "playing on {}", device.name),
+            PlaybackEvent::Stopped => write!(f, "stopped"),
+            PlaybackEvent::AuthLost => write!(f, "login lost"),
+        }
+    }
+}
+
+impl PlaybackEvent {
+    /// The item that started, `None` unless a different item started
+    pub fn item(&self) -> Option<PlayingItem> {
+        match self {
+            PlaybackEvent::TrackChanged(track) => Some(PlayingItem::Track(track.clone())),
+            PlaybackEvent::EpisodeChanged(episode) => Some(PlayingItem::Episode(episode.clone())),
+            PlaybackEvent::ChapterChanged(chapter) => Some(PlayingItem::Chapter(chapter.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// Polls a [`NowPlayingSource`] and turns successive states into [`PlaybackEvent`]s
+pub struct Poller<S> {
+    source: S,
+    interval: Duration,
+    /// State from the last successful poll, `None` before the first one
+    previous: Option<Option<PlaybackState>>,
+    last_poll: Instant,
+    /// Set once [`PlaybackEvent::AuthLost`] was sent so it is only sent once
+    auth_lost: bool,
+}
+
+impl<S: NowPlayingSource> Poller<S> {
+    pub fn new(source: S, interval: Duration) -> Poller<S> {
+        Poller {
+            source,
+            interval,
+            previous: None,
+            last_poll: Instant::now(),
+            auth_lost: false,
+        }
+    }
+
+    /// The source being polled, e.g. to request the listening history
+    pub fn source_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// State from the last successful poll
+    pub fn state(&self) -> Option<&PlaybackState> {
+        self.previous.as_ref().and_then(|state| state.as_ref())
+    }
+
+    /// How long to wait between polls
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Polls the source once, returning what changed since the last poll
+    pub async fn poll(&mut self) -> Result<Vec<PlaybackEvent>, SourceError> {
+        let current = match self.source.current_playback().await {
+            Ok(current) => current,
+            Err(SourceError::AuthLost(_)) if !self.auth_lost => {
+                self.auth_lost = true;
+                return Ok(vec![PlaybackEvent::AuthLost]);
+            }
+            Err(e) => return Err(e),
+        };
+        self.auth_lost = false;
+
+        let elapsed = self.last_poll.elapsed();
+        self.last_poll = Instant::now();
+        let events = match &self.previous {
+            Some(previous) => diff(previous.as_ref(), current.as_ref(), elapsed),
+            None => diff_first(current.as_ref()),
+        };
+        self.previous = Some(current);
+        Ok(events)
+    }
+
+    /// Polls forever, yielding each event as it is derived
+    ///
+    /// Errors other than a lost login are logged and the poll is retried after the interval
+    pub fn events(self) -> impl Stream<Item = PlaybackEvent>
+    where
+        S: 'static,
+    {
+        let pending: VecDeque<PlaybackEvent> = VecDeque::new();
+        stream::unfold((self, pending, true), |(mut poller, mut pending, mut first)| async move {
+            loop {
+                if let Some(event) = pending.pop_front() {
+                    return Some((event, (poller, pending, first)));
+                }
+                if !first {
+                    sleep(poller.interval).await;
+                }
+                first = false;
+                match poller.poll().await {
+                    Ok(events) => pending.extend(events),
+                    Err(e) => println!("Could not fetch playback: {e}"),
+                }
+            }
+        })
+    }
+}
+
+/// Events for the very first state, so consumers learn what is already playing
+fn diff_first(current: Option<&PlaybackState>) -> Vec<PlaybackEvent> {
+    match current.and_then(|state| state.drawable_item()) {
+        Some(item) => {
+            let mut events = item_changed(item).into_iter().collect::<Vec<_>>();
+            if let Some(device) = current.and_then(|state| state.device.clone()) {
+                events.push(PlaybackEvent::DeviceChanged(device));
+            }
+            if current.is_some_and(|state| !state.is_playing) {
+                events.push(PlaybackEvent::Paused);
+            }
+            events
+        }
+        None => vec![PlaybackEvent::Stopped],
+    }
+}
+
+/// Events that turn `previous` into `current`, `elapsed` being the time between both polls
+fn diff(previous: Option<&PlaybackState>, current: Option<&PlaybackState>, elapsed: Duration) -> Vec<PlaybackEvent> {
+    let mut events = Vec::new();
+    let previous_item = previous.and_then(|state| state.drawable_item());
+    let Some(state) = current.filter(|state| state.drawable_item().is_some()) else {
+        if previous_item.is_some() {
+            events.push(PlaybackEvent::Stopped);
+        }
+        return events;
+    };
+    let item = state.drawable_item().expect("Filtered above");
+
+    let same_item = previous_item.is_some_and(|previous| previous.same_as(item));
+    if !same_item {
+        events.extend(item_changed(item));
+    }
+
+    let previous_device = previous.and_then(|state| state.device.as_ref());
+    if let Some(device) = &state.device {
+        if previous_device.is_none_or(|previous| previous.id != device.id) {
+            events.push(PlaybackEvent::DeviceChanged(device.clone()));
+        }
+    }
+
+    let was_playing = previous.is_some_and(|state| state.is_playing);
+    match (previous_item.is_some(), was_playing, state.is_playing) {
+        // Something new showing up paused is reported as paused too
+        (true, true, false) | (false, _, false) => events.push(PlaybackEvent::Paused),
+        (true, false, true) => events.push(PlaybackEvent::Resumed),
+        _ => {}
+    }
+
+    // Only a jump within the same item is a seek, a new item always starts somewhere
+    if same_item {
+        if let (Some(before), Some(now)) = (previous.and_then(|state| state.progress_ms), state.progress_ms) {
+            let expected = if was_playing { before + elapsed.as_millis() as u64 } else { before };
+            if now.abs_diff(expected) > SEEK_TOLERANCE_MS {
+                events.push(PlaybackEvent::Seeked { position_ms: now });
+            }
+        }
+    }
+    events
+}
+
+fn item_changed(item: &PlayingItem) -> Option<PlaybackEvent> {
+    match item {
+        PlayingItem::Track(track) => Some(PlaybackEvent::TrackChanged(track.clone())),
+        PlayingItem::Episode(episode) => Some(PlaybackEvent::EpisodeChanged(episode.clone())),
+        PlayingItem::Chapter(chapter) => Some(PlaybackEvent::ChapterChanged(chapter.clone())),
+        PlayingItem::Unsupported => None,
+    }
+}
+
+/// Prints every event from `source`, runs `hooks` on it and logs it to `history` until Ctrl+C is pressed
+pub async fn watch(source: impl NowPlayingSource + 'static, hooks: Hooks, mut history: Option<HistoryLogger>) {
+    let events = Poller::new(source, DEFAULT_INTERVAL).events();
+    futures::pin_mut!(events);
+    let shutdown = tokio::signal::ctrl_c();
+    futures::pin_mut!(shutdown);
+    loop {
+        let event = tokio::select! {
+            event = events.next() => event,
+            _ = &mut shutdown => None,
+        };
+        let Some(event) = event else { break };
+        println!("{}", event);
+        hooks.on_event(&event);
+        if let Some(history) = &mut history {
+            history.on_event(&event);
+        }
+    }
+    // The item still playing would otherwise be lost
+    if let Some(history) = history {
+        history.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MockSource;
+
+    /// A player state with track `name` on device `device`
+    fn state(name: &str, playing: bool, progress_ms: u64, device: &str) -> PlaybackState {
+        serde_json::from_value(serde_json::json!({
+            "item": {
+                "type": "track",
+                "uri": format!("spotify:track:{name}"),
+                "name": name,
+                "artists": [{ "name": "Artist" }],
+                "album": { "name": "Album", "images": [] },
+                "duration_ms": 200_000,
+            },
+            "is_playing": playing,
+            "progress_ms": progress_ms,
+            "device": { "id": device, "name": device },
+        }))
+        .expect("Could not build state")
+    }
+
+    fn names(events: &[PlaybackEvent]) -> Vec<String> {
+        events.iter().map(PlaybackEvent::to_string).collect()
+    }
+
+    /// A state playing something the screensaver cannot draw, such as an ad
+    fn unsupported() -> PlaybackState {
+        serde_json::from_value(serde_json::json!({ "item": { "type": "ad" }, "is_playing": true }))
+            .expect("Could not build state")
+    }
+
+    #[test]
+    fn diff_first_reports_what_is_already_playing() {
+        let cases: Vec<(&str, Option<PlaybackState>, Vec<&str>)> = vec![
+            ("nothing", None, vec!["stopped"]),
+            ("undrawable", Some(unsupported()), vec!["stopped"]),
+            ("playing", Some(state("A", true, 0, "Desk")), vec!["track changed: A - Artist", "playing on Desk"]),
+            ("paused", Some(state("A", false, 0, "Desk")), vec!["track changed: A - Artist", "playing on Desk", "paused"]),
+        ];
+        for (case, current, expected) in cases {
+            assert_eq!(names(&diff_first(current.as_ref())), expected, "{case}");
+        }
+    }
+
+    #[test]
+    fn diff_reports_changes_between_polls() {
+        let mut renamed = state("A", true, 2000, "Desk");
+        renamed.device.as_mut().expect("Has a device").name = String::from("Renamed desk");
+        // Name, previous poll, current poll, seconds between them, expected events
+        type Case = (&'static str, Option<PlaybackState>, Option<PlaybackState>, u64, Vec<&'static str>);
+        let cases: Vec<Case> = vec![
+            ("still nothing", None, None, 2, vec![]),
+            ("stopped", Some(state("A", true, 0, "Desk")), None, 2, vec!["stopped"]),
+            ("stopped on something undrawable", Some(state("A", true, 0, "Desk")), Some(unsupported()), 2, vec!["stopped"]),
+            ("undrawable is not a stop twice", Some(unsupported()), None, 2, vec![]),
+            ("started", None, Some(state("A", true, 0, "Desk")), 2, vec!["track changed: A - Artist", "playing on Desk"]),
+            (
+                "arrived paused",
+                None,
+                Some(state("A", false, 0, "Desk")),
+                2,
+                vec!["track changed: A - Artist", "playing on Desk", "paused"],
+            ),
+            ("paused", Some(state("A", true, 0, "Desk")), Some(state("A", false, 2000, "Desk")), 2, vec!["paused"]),
+            ("resumed", Some(state("A", false, 2000, "Desk")), Some(state("A", true, 2000, "Desk")), 2, vec!["resumed"]),
+            ("next track", Some(state("A", true, 0, "Desk")), Some(state("B", true, 0, "Desk")), 2, vec!["track changed: B - Artist"]),
+            ("new items never seek", Some(state("A", true, 0, "Desk")), Some(state("B", true, 90_000, "Desk")), 2, vec!["track changed: B - Artist"]),
+            ("moved device", Some(state("A", true, 0, "Desk")), Some(state("A", true, 2000, "Phone")), 2, vec!["playing on Phone"]),
+            ("devices are told apart by id", Some(state("A", true, 0, "Desk")), Some(renamed), 2, vec![]),
+            ("played on", Some(state("A", true, 0, "Desk")), Some(state("A", true, 2000, "Desk")), 2, vec![]),
+            ("drift within tolerance", Some(state("A", true, 0, "Desk")), Some(state("A", true, 4500, "Desk")), 2, vec![]),
+            ("seeked ahead", Some(state("A", true, 0, "Desk")), Some(state("A", true, 60_000, "Desk")), 2, vec!["seeked to 60s"]),
+            ("seeked back", Some(state("A", true, 60_000, "Desk")), Some(state("A", true, 10_000, "Desk")), 2, vec!["seeked to 10s"]),
+            ("paused stays put", Some(state("A", false, 10_000, "Desk")), Some(state("A", false, 10_000, "Desk")), 5, vec![]),
+            ("seeked while paused", Some(state("A", false, 10_000, "Desk")), Some(state("A", false, 20_000, "Desk")), 5, vec!["seeked to 20s"]),
+        ];
+        for (case, previous, current, elapsed, expected) in cases {
+            let events = diff(previous.as_ref(), current.as_ref(), Duration::from_secs(elapsed));
+            assert_eq!(names(&events), expected, "{case}");
+        }
+    }
+
+    #[tokio::test]
+    async fn poller_follows_a_mock_script() {
+        let steps = vec![
+            Some(state("A", true, 0, "Desk")),
+            Some(state("A", false, 500, "Desk")),
+            Some(state("B", true, 0, "Desk")),
+            None,
+        ];
+        let mut poller = Poller::new(MockSource::new(steps, Vec::new(), Vec::new(), Vec::new()), Duration::ZERO);
+        let mut polls = Vec::new();
+        // The mock repeats its last step once the script runs out
+        for _ in 0..5 {
+            polls.push(names(&poller.poll().await.expect("Mock never fails")));
+        }
+        assert_eq!(polls, vec![
+            vec!["track changed: A - Artist", "playing on Desk"],
+            vec!["paused"],
+            vec!["track changed: B - Artist", "resumed"],
+            vec!["stopped"],
+            Vec::<&str>::new(),
+        ]);
+        assert!(poller.state().is_none());
+    }
+}
@@ -0,0 +1,377 @@
+use chrono::{DateTime, Local, NaiveDate, SecondsFormat, TimeZone};
+use serde::{ Serialize, Deserialize };
+
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::events::PlaybackEvent;
+use crate::settings::{HistorySettings, HistoryWriter};
+
+/// Plays shorter than this are treated as skips and not recorded
+const MIN_LISTENED: Duration = Duration::from_secs(5);
+/// Separates artist names in the history file, since names may contain commas
+const ARTIST_SEPARATOR: &str = "; ";
+
+/// Represents one line of the history file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryRecord {
+    /// RFC 3339 timestamp of when the item started playing
+    pub played_at: String,
+    /// `track`, `local`, `episode` or `chapter`
+    pub kind: String,
+    pub name: String,
+    /// Artist names separated by `; `, the show's name for episodes and the authors for chapters
+    pub artists: String,
+    /// Album name, the show's name for episodes and the audiobook's for chapters
+    pub album: String,
+    /// Seconds the item was actually playing, pauses excluded
+    pub listened_secs: u64,
+    /// Name of the device it played on, empty when unknown
+    pub device: String,
+    pub uri: String,
+}
+
+/// Represents the item currently being listened to
+struct Listening {
+    record: HistoryRecord,
+    /// Playing time before the last pause
+    listened: Duration,
+    /// When playback last resumed, `None` while paused
+    resumed_at: Option<Instant>,
+}
+
+impl Listening {
+    fn listened(&self) -> Duration {
+        self.listened + self.resumed_at.map_or(Duration::ZERO, |resumed| resumed.elapsed())
+    }
+}
+
+/// Appends every played track, episode and chapter to the history file
+pub struct HistoryLogger {
+    path: PathBuf,
+    current: Option<Listening>,
+    /// Last device reported, new items start out on it
+    device: String,
+}
+
+impl HistoryLogger {
+    /// Creates a logger for the configured file, `None` if logging is disabled or left to another writer
+    pub fn from_settings(settings: &HistorySettings, writer: HistoryWriter) -> Option<HistoryLogger> {
+        (settings.enabled && settings.writer == writer).then(|| HistoryLogger {
+            path: settings.path.clone(),
+            current: None,
+            device: String::new(),
+        })
+    }
+
+    /// Updates the item being listened to, writing the previous one out once it is finished
+    pub fn on_event(&mut self, event: &PlaybackEvent) {
+        if let Some(item) = event.item() {
+            return self.start(HistoryRecord {
+                played_at: Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+                kind: item.kind().to_string(),
+                name: item.name().to_string(),
+                artists: item.creators(ARTIST_SEPARATOR),
+                album: item.collection().to_string(),
+                listened_secs: 0,
+                device: self.device.clone(),
+                uri: item.uri().to_string(),
+            });
+        }
+        match event {
+            // Handled above
+            PlaybackEvent::TrackChanged(_) | PlaybackEvent::EpisodeChanged(_) | PlaybackEvent::ChapterChanged(_) => {}
+            PlaybackEvent::Paused => {
+                if let Some(current) = &mut self.current {
+                    current.listened = current.listened();
+                    current.resumed_at = None;
+                }
+            }
+            PlaybackEvent::Resumed => {
+                if let Some(current) = &mut self.current {
+                    current.resumed_at.get_or_insert_with(Instant::now);
+                }
+            }
+            PlaybackEvent::DeviceChanged(device) => {
+                self.device = device.name.clone();
+                if let Some(current) = &mut self.current {
+                    current.record.device = device.name.clone();
+                }
+            }
+            PlaybackEvent::Stopped => self.finish(),
+            PlaybackEvent::Seeked { .. } | PlaybackEvent::AuthLost => {}
+        }
+    }
+
+    /// Writes out the item being listened to, called when the command doing the logging stops
+    pub fn close(mut self) {
+        self.finish();
+    }
+
+    fn start(&mut self, record: HistoryRecord) {
+        self.finish();
+        self.current = Some(Listening {
+            record,
+            listened: Duration::ZERO,
+            resumed_at: Some(Instant::now()),
+        });
+    }
+
+    /// Writes the current item out, unless it was skipped
+    fn finish(&mut self) {
+        let Some(current) = self.current.take() else { return };
+        let listened = current.listened();
+        if listened < MIN_LISTENED {
+            return;
+        }
+        let record = HistoryRecord {
+            listened_secs: listened.as_secs(),
+            ..current.record
+        };
+        if let Err(e) = self.append(&record) {
+            println!("Could not write to {}: {e}", self.path.display());
+        }
+    }
+
+    fn append(&self, record: &HistoryRecord) -> Result<(), csv::Error> {
+        // Only a new file needs the header row
+        let is_new = fs::metadata(&self.path).map_or(true, |metadata| metadata.len() == 0);
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let mut writer = csv::WriterBuilder::new().has_headers(is_new).from_writer(file);
+        writer.serialize(record)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Output format of [`export`]
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn parse(value: &str) -> Option<Format> {
+        match value {
+            "csv" => Some(Format::Csv),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a `--from`/`--to` bound, either a local `YYYY-MM-DD` date or an RFC 3339 timestamp
+///
+/// Dates cover the whole day, so `end_of_day` picks midnight of the following day
+pub fn parse_bound(value: &str, end_of_day: bool) -> Option<DateTime<Local>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Some(timestamp.with_timezone(&Local));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let date = if end_of_day { date.succ_opt()? } else { date };
+    Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest()
+}
+
+/// Reads the records in the history file played within `[from, to)`
+pub fn read(settings: &HistorySettings, from: Option<DateTime<Local>>, to: Option<DateTime<Local>>) -> Result<Vec<HistoryRecord>, csv::Error> {
+    let mut reader = csv::Reader::from_path(&settings.path)?;
+    let mut records = Vec::new();
+    for record in reader.deserialize::<HistoryRecord>() {
+        let record = record?;
+        let Ok(played_at) = DateTime::parse_from_rfc3339(&record.played_at) else { continue };
+        if from.is_some_and(|from| played_at < from) || to.is_some_and(|to| played_at >= to) {
+            continue;
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Writes `records` to standard output
+pub fn export(records: &[HistoryRecord], format: Format) -> Result<(), csv::Error> {
+    match format {
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(io::stdout());
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        Format::Json => {
+            println!("{}", serde_json::to_string_pretty(records).expect("Could not convert"));
+        }
+    }
+    Ok(())
+}
+
+/// Represents how much something was listened to
+#[derive(Default, Debug)]
+struct Tally {
+    plays: u32,
+    listened_secs: u64,
+}
+
+/// An album's name and its first artist
+type AlbumKey<'a> = (&'a str, &'a str);
+
+/// Tallies the artists and the albums with their first artist over the tracks in `records`
+///
+/// Local files may have no artist, which is not counted as an artist of its own
+fn tally(records: &[HistoryRecord]) -> (HashMap<&str, Tally>, HashMap<AlbumKey<'_>, Tally>) {
+    let mut artists: HashMap<&str, Tally> = HashMap::new();
+    let mut albums: HashMap<AlbumKey, Tally> = HashMap::new();
+    for record in records.iter().filter(|record| matches!(record.kind.as_str(), "track" | "local")) {
+        for artist in record.artists.split(ARTIST_SEPARATOR).filter(|artist| !artist.is_empty()) {
+            let tally = artists.entry(artist).or_default();
+            tally.plays += 1;
+            tally.listened_secs += record.listened_secs;
+        }
+        let first_artist = record.artists.split(ARTIST_SEPARATOR).next().unwrap_or_default();
+        let tally = albums.entry((&record.album, first_artist)).or_default();
+        tally.plays += 1;
+        tally.listened_secs += record.listened_secs;
+    }
+    (artists, albums)
+}
+
+/// Prints the `top` most played artists and albums in `records`
+pub fn print_stats(records: &[HistoryRecord], top: usize) {
+    let (artists, albums) = tally(records);
+    let total: u64 = records.iter().map(|record| record.listened_secs).sum();
+    println!("{} plays, {} minutes listened", records.len(), total / 60);
+
+    println!("\nTop artists:");
+    for (artist, tally) in ranked(artists, top) {
+        println!("  {} - {} plays, {} min", artist, tally.plays, tally.listened_secs / 60);
+    }
+    println!("\nTop albums:");
+    for ((album, artist), tally) in ranked(albums, top) {
+        println!("  {} by {} - {} plays, {} min", album, artist, tally.plays, tally.listened_secs / 60);
+    }
+}
+
+/// The `top` entries with the most plays, ties broken by listening time
+fn ranked<K>(tallies: HashMap<K, Tally>, top: usize) -> Vec<(K, Tally)> {
+    let mut ranked: Vec<(K, Tally)> = tallies.into_iter().collect();
+    ranked.sort_by(|(_, a), (_, b)| b.plays.cmp(&a.plays).then(b.listened_secs.cmp(&a.listened_secs)));
+    ranked.truncate(top);
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(played_at: &str, kind: &str, artists: &str, album: &str, listened_secs: u64) -> HistoryRecord {
+        HistoryRecord {
+            played_at: played_at.to_string(),
+            kind: kind.to_string(),
+            name: String::from("Song"),
+            artists: artists.to_string(),
+            album: album.to_string(),
+            listened_secs,
+            device: String::new(),
+            uri: String::from("spotify:track:1"),
+        }
+    }
+
+    /// Settings for a history file of its own in the temporary directory, removing any left over
+    fn temp_settings(name: &str) -> HistorySettings {
+        let path = std::env::temp_dir().join(format!("spotify_user_{}_{name}.csv", std::process::id()));
+        let _ = fs::remove_file(&path);
+        HistorySettings { path, ..HistorySettings::default() }
+    }
+
+    fn logger(settings: &HistorySettings, listened: Duration, playing_for: Option<Duration>) -> HistoryLogger {
+        let mut logger = HistoryLogger::from_settings(settings, HistoryWriter::Saver).expect("Expected a logger");
+        logger.current = Some(Listening {
+            record: record("2024-03-10T12:00:00+00:00", "track", "Artist", "Album", 0),
+            listened,
+            resumed_at: playing_for.map(|elapsed| Instant::now() - elapsed),
+        });
+        logger
+    }
+
+    #[test]
+    fn pauses_are_not_counted_as_listening() {
+        let settings = temp_settings("pauses");
+        let mut logger = logger(&settings, Duration::from_secs(20), Some(Duration::from_secs(10)));
+
+        logger.on_event(&PlaybackEvent::Paused);
+        let current = logger.current.as_ref().expect("Expected an item");
+        assert_eq!(current.resumed_at, None);
+        let paused = current.listened();
+        assert!(paused >= Duration::from_secs(30) && paused < Duration::from_secs(31), "{paused:?}");
+
+        logger.on_event(&PlaybackEvent::Resumed);
+        let resumed_at = logger.current.as_ref().and_then(|current| current.resumed_at);
+        assert!(resumed_at.is_some());
+        // A second resume while already playing keeps the first one
+        logger.on_event(&PlaybackEvent::Resumed);
+        assert_eq!(logger.current.as_ref().and_then(|current| current.resumed_at), resumed_at);
+        assert!(logger.current.as_ref().unwrap().listened() >= paused);
+    }
+
+    #[test]
+    fn short_plays_are_not_recorded() {
+        let settings = temp_settings("short");
+        logger(&settings, Duration::from_secs(2), Some(Duration::from_secs(2))).close();
+        assert!(!settings.path.exists());
+
+        logger(&settings, Duration::from_secs(4), Some(Duration::from_secs(2))).close();
+        let records = read(&settings, None, None).expect("Expected the history to be written");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].listened_secs, 6);
+        let _ = fs::remove_file(&settings.path);
+    }
+
+    #[test]
+    fn dates_cover_the_whole_day() {
+        let start = parse_bound("2024-03-10", false).expect("Expected a date");
+        let end = parse_bound("2024-03-10", true).expect("Expected a date");
+        assert_eq!(start.naive_local(), NaiveDate::from_ymd_opt(2024, 3, 10).unwrap().and_hms_opt(0, 0, 0).unwrap());
+        assert_eq!(end.naive_local(), NaiveDate::from_ymd_opt(2024, 3, 11).unwrap().and_hms_opt(0, 0, 0).unwrap());
+
+        let timestamp = parse_bound("2024-03-10T15:30:00+00:00", true).expect("Expected a timestamp");
+        assert_eq!(timestamp.to_rfc3339(), DateTime::parse_from_rfc3339("2024-03-10T15:30:00+00:00").unwrap().with_timezone(&Local).to_rfc3339());
+        assert!(parse_bound("10/03/2024", false).is_none());
+    }
+
+    #[test]
+    fn read_keeps_plays_within_the_bounds() {
+        let settings = temp_settings("read");
+        let logger = HistoryLogger::from_settings(&settings, HistoryWriter::Saver).expect("Expected a logger");
+        for played_at in ["2024-03-09T12:00:00+00:00", "2024-03-10T12:00:00+00:00", "2024-03-11T12:00:00+00:00", "not a time"] {
+            logger.append(&record(played_at, "track", "Artist", "Album", 60)).expect("Expected the record to be written");
+        }
+
+        let played = |from: Option<&str>, to: Option<&str>| -> Vec<String> {
+            let from = from.map(|value| DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Local));
+            let to = to.map(|value| DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Local));
+            read(&settings, from, to).unwrap().into_iter().map(|record| record.played_at).collect()
+        };
+        assert_eq!(played(None, None).len(), 3);
+        assert_eq!(played(Some("2024-03-10T12:00:00+00:00"), None), ["2024-03-10T12:00:00+00:00", "2024-03-11T12:00:00+00:00"]);
+        assert_eq!(played(None, Some("2024-03-10T12:00:00+00:00")), ["2024-03-09T12:00:00+00:00"]);
+        assert_eq!(played(Some("2024-03-10T00:00:00+00:00"), Some("2024-03-11T00:00:00+00:00")), ["2024-03-10T12:00:00+00:00"]);
+        let _ = fs::remove_file(&settings.path);
+    }
+
+    #[test]
+    fn empty_artists_are_not_tallied() {
+        let records = [
+            record("2024-03-10T12:00:00+00:00", "track", "A; B", "One", 60),
+            record("2024-03-10T12:05:00+00:00", "local", "", "Tape", 60),
+            record("2024-03-10T12:10:00+00:00", "episode", "Show", "Show", 60),
+        ];
+        let (artists, albums) = tally(&records);
+        let mut names: Vec<&str> = artists.keys().copied().collect();
+        names.sort();
+        assert_eq!(names, ["A", "B"]);
+        assert_eq!(albums.len(), 2);
+    }
+}
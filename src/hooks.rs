@@ -0,0 +1,218 @@
+use serde::Serialize;
+use tokio::{process::Command, sync::Semaphore, time::timeout};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::events::PlaybackEvent;
+use crate::playback::Image;
+use crate::settings::HookSettings;
+
+/// Side of the cover handed to hooks, the largest Spotify usually offers
+const COVER_SIDE: u32 = 640;
+
+/// Represents the details of a new track, episode or chapter handed to hooks
+#[derive(Serialize, Debug, Clone)]
+struct HookPayload {
+    /// `track`, `local`, `episode` or `chapter`
+    r#type: &'static str,
+    name: String,
+    /// Artist names joined with commas, the show's name for episodes and the authors for chapters
+    artists: String,
+    /// Album name, the show's name for episodes and the audiobook's for chapters
+    album: String,
+    cover_url: String,
+    uri: String,
+}
+
+impl HookPayload {
+    fn from_event(event: &PlaybackEvent) -> Option<HookPayload> {
+        let item = event.item()?;
+        Some(HookPayload {
+            r#type: item.kind(),
+            name: item.name().to_string(),
+            artists: item.creators(", "),
+            album: item.collection().to_string(),
+            cover_url: Image::best_fit(item.images(), COVER_SIDE).map(|image| image.url.clone()).unwrap_or_default(),
+            uri: item.uri().to_string(),
+        })
+    }
+
+    fn environment(&self) -> [(&'static str, &str); 6] {
+        [
+            ("SPOTIFY_TYPE", self.r#type),
+            ("SPOTIFY_NAME", &self.name),
+            ("SPOTIFY_ARTISTS", &self.artists),
+            ("SPOTIFY_ALBUM", &self.album),
+            ("SPOTIFY_COVER_URL", &self.cover_url),
+            ("SPOTIFY_URI", &self.uri),
+        ]
+    }
+}
+
+/// Runs the user's commands when the playing track or episode changes
+pub struct Hooks {
+    settings: HookSettings,
+    /// Limits how many commands run at once
+    permits: Arc<Semaphore>,
+}
+
+impl Hooks {
+    pub fn new(settings: HookSettings) -> Hooks {
+        let permits = Arc::new(Semaphore::new(settings.max_concurrent.max(1)));
+        Hooks { settings, permits }
+    }
+
+    /// Starts the configured commands in the background if `event` is a track or episode change
+    ///
+    /// Must be called from inside a tokio runtime
+    pub fn on_event(&self, event: &PlaybackEvent) {
+        let Some(payload) = HookPayload::from_event(event) else { return };
+
+        if let Some(fifo) = &self.settings.fifo {
+            let fifo = fifo.clone();
+            let line = serde_json::to_string(&payload).expect("Could not convert");
+            tokio::spawn(async move { write_fifo(fifo, line).await });
+        }
+
+        for command in &self.settings.commands {
+            // Skip rather than queue, by the time a slot frees up the track is likely stale
+            let Ok(permit) = self.permits.clone().try_acquire_owned() else {
+                println!("Skipping hook, {} already running", self.settings.max_concurrent);
+                continue;
+            };
+            let command = command.clone();
+            let payload = payload.clone();
+            let limit = Duration::from_secs(self.settings.timeout_secs);
+            tokio::spawn(async move {
+                run_command(&command, &payload, limit).await;
+                drop(permit);
+            });
+        }
+    }
+}
+
+/// Runs `command` through the system shell, killing it if it outlives `limit`
+async fn run_command(command: &str, payload: &HookPayload, limit: Duration) {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let child = shell
+        .arg(command)
+        .envs(payload.environment())
+        .kill_on_drop(true)
+        .spawn();
+
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            println!("Could not run hook `{command}`: {e}");
+            return;
+        }
+    };
+    match timeout(limit, child.wait()).await {
+        Ok(Ok(status)) if !status.success() => println!("Hook `{command}` exited with {status}"),
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => println!("Hook `{command}` failed: {e}"),
+        Err(_) => {
+            println!("Hook `{command}` timed out after {}s", limit.as_secs());
+            let _ = child.kill().await;
+        }
+    }
+}
+
+/// Writes `line` to the FIFO at `path`, doing nothing while no reader has it open
+#[cfg(unix)]
+async fn write_fifo(path: std::path::PathBuf, line: String) {
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::unix::pipe;
+
+    // Opening the sender fails right away instead of blocking when there is no reader
+    let Ok(mut sender) = pipe::OpenOptions::new().open_sender(&path) else { return };
+    if let Err(e) = sender.write_all(format!("{line}\n").as_bytes()).await {
+        println!("Could not write to {}: {e}", path.display());
+    }
+}
+
+#[cfg(not(unix))]
+async fn write_fifo(path: std::path::PathBuf, _line: String) {
+    println!("Cannot write to {}, FIFOs are only supported on Unix", path.display());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playback::Track;
+
+    fn payload() -> HookPayload {
+        HookPayload {
+            r#type: "track",
+            name: String::from("Song"),
+            artists: String::from("A, B"),
+            album: String::from("Album"),
+            cover_url: String::from("https://i.scdn.co/image/large"),
+            uri: String::from("spotify:track:1"),
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("spotify_user_{}_{name}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn the_cover_fitting_the_largest_side_is_handed_on() {
+        let track: Track = serde_json::from_value(serde_json::json!({
+            "type": "track",
+            "uri": "spotify:track:1",
+            "name": "Song",
+            "artists": [{ "name": "A" }, { "name": "B" }],
+            "album": { "name": "Album", "images": [
+                { "url": "small", "width": 64, "height": 64 },
+                { "url": "large", "width": 640, "height": 640 },
+                { "url": "medium", "width": 300, "height": 300 },
+            ] },
+            "duration_ms": 200_000,
+        }))
+        .expect("Could not build track");
+        let payload = HookPayload::from_event(&PlaybackEvent::TrackChanged(track)).expect("Expected a payload");
+        assert_eq!(payload.cover_url, "large");
+        assert_eq!(payload.artists, "A, B");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn commands_see_the_details_as_environment_variables() {
+        let path = temp_path("hook_environment");
+        let command = format!(
+            "printf '%s|%s|%s|%s|%s|%s' \"$SPOTIFY_TYPE\" \"$SPOTIFY_NAME\" \"$SPOTIFY_ARTISTS\" \"$SPOTIFY_ALBUM\" \"$SPOTIFY_COVER_URL\" \"$SPOTIFY_URI\" > {}",
+            path.display()
+        );
+        run_command(&command, &payload(), Duration::from_secs(5)).await;
+        let written = std::fs::read_to_string(&path).expect("Expected the hook to write");
+        assert_eq!(written, "track|Song|A, B|Album|https://i.scdn.co/image/large|spotify:track:1");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn commands_running_too_long_are_killed() {
+        let path = temp_path("hook_pid");
+        let command = format!("echo $$ > {}; exec sleep 30", path.display());
+        let started = std::time::Instant::now();
+        run_command(&command, &payload(), Duration::from_millis(200)).await;
+        assert!(started.elapsed() < Duration::from_secs(5), "{:?}", started.elapsed());
+
+        let pid = std::fs::read_to_string(&path).expect("Expected the hook to write its pid");
+        let alive = Command::new("kill").args(["-0", pid.trim()]).stderr(std::process::Stdio::null()).status().await.expect("Could not run kill");
+        assert!(!alive.success(), "hook {} is still running", pid.trim());
+        let _ = std::fs::remove_file(&path);
+    }
+}
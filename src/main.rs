@@ -1,33 +1,80 @@
 use std::{fs, path::{Path, PathBuf}, process::Command};
 
 use iced::{
-    alignment::{Horizontal::{self}}, widget::{ button, container, row, text, text_input, Container }, window::{self}, Alignment::Center, Color, Font, Length, Task
+    alignment::{Horizontal::{self}}, widget::{ button, checkbox, container, image, pick_list, row, scrollable, slider, text, text_input, Canvas, Container }, window::{self}, Alignment::Center, Color, Font, Length, Subscription, Task
 };
+use std::time::Instant;
 use iced::widget::column;
 use rfd::FileDialog;
 
+use crate::history::HistoryLogger;
+use crate::hooks::Hooks;
+use crate::preview::Preview;
+use crate::scope::{Features, Scope};
+use crate::settings::{Celebration, DisplayMode, HistoryWriter, IdleScene, InfoSettings, SaverSettings, Settings, Tint, TransitionSettings, TransitionStyle, SETTINGS_FILE};
+use crate::playback::{Device, PlaybackCommand, RepeatMode};
+use crate::source::{NowPlayingSource, SourceKind, SpotifySource};
 use crate::spotify::{SpotifyUser};
+mod artwork;
+mod bounce;
+mod display;
+mod events;
+mod history;
+mod hooks;
+mod palette;
+mod playback;
+mod preview;
+mod renderer;
+mod scope;
+mod server;
+mod settings;
+mod source;
 mod spotify;
+mod transition;
 
+/// Side of the artwork fetched for the settings preview, in physical pixels
+const PREVIEW_ART_SIDE: u32 = 300;
+/// Whether the installer builds the macOS saver, which only reads the size, speed and polling interval
+const BASIC_SAVER: bool = cfg!(target_os = "macos");
 
 
 
+
+#[derive(Default)]
 enum Status {
     UserSelect,
+    Settings,
+    #[default]
     SignIn,
     SuccessPage,
 }
-impl Default for Status {
-    fn default() -> Self {
-        Status::SignIn
-    }
-}
 
 #[derive(Clone, Debug)]
 enum Message {
     NextPage,
     InputID(String),
     InputSecret(String),
+    ToggleRecentlyPlayed(bool),
+    ToggleLibrary(bool),
+    TogglePlaybackControl(bool),
+    OpenSettings,
+    CloseSettings,
+    SetSize(f32),
+    SetSpeed(f32),
+    ToggleInfo(bool),
+    ToggleProgressBar(bool),
+    ToggleProgressTimes(bool),
+    TogglePredictCorners(bool),
+    ToggleTrail(bool),
+    ToggleInteractive(bool),
+    ToggleShowDevice(bool),
+    SelectTransition(TransitionStyle),
+    SetTransitionDuration(u32),
+    SelectIdle(IdleScene),
+    SetPollInterval(u32),
+    SelectTint(Tint),
+    SelectDisplays(DisplayMode),
+    PreviewTick(Instant),
     ToSelection,
     SelectBuild,
     SelectOutput,
@@ -44,11 +91,21 @@ struct LoginMenu {
     build_status: (String, bool),
     output_dir: PathBuf,
     output_status: (String, bool),
-    sign_in_message: String
+    sign_in_message: String,
+    features: Features,
+    /// Scopes granted to a previously stored login, if there is one
+    stored_scope: Option<Scope>,
+    /// Profile picture of the signed in account
+    avatar: Option<image::Handle>,
+    /// Screensaver settings, written to settings.json when the saver is built
+    settings: Settings,
+    /// Screensaver drawn with `settings` on the settings page
+    preview: Preview
 }
 
 impl Default for LoginMenu {
     fn default() -> Self {
+        let settings = Settings::load();
         Self { 
             client: Default::default(), 
             content: Default::default(), 
@@ -69,7 +126,12 @@ impl Default for LoginMenu {
             build_status: (String::default(), false),
             output_dir: std::env::current_exe().unwrap().parent().expect("Cannot find parent").to_path_buf(),
             output_status: (String::default(), false),
-            sign_in_message: String::default()
+            sign_in_message: String::default(),
+            features: Features::default(),
+            stored_scope: SpotifyUser::granted_scope(),
+            avatar: None,
+            preview: Preview::new(&previewed(&settings.saver)),
+            settings
         }
     }
 }
@@ -94,16 +156,36 @@ impl LoginMenu {
         match self.content {
             Status::UserSelect => {
                 let build_red = if self.build_status.1 {Color::from_rgb(255.0, 255.0,255.0)} else {Color::from_rgb(100.0, 0.0, 0.0)};
+                let product = self.client.profile().and_then(|profile| profile.product);
+                let tier = product.map_or("Unknown", |product| product.label());
+                let free_warning = if product.is_some_and(|product| product.is_free()) {
+                    "Spotify Free accounts cannot control playback and some playback details are limited"
+                } else {
+                    ""
+                };
+                let avatar = match &self.avatar {
+                    Some(handle) => container(image(handle.clone()).width(48).height(48)),
+                    None => container(text("")),
+                };
                 container(
                     column![
-                        text(format!("Successfully found account: {}", self.client.get_username())).size(15),
+                        row![
+                            avatar,
+                            column![
+                                text(format!("Successfully found account: {}", self.client.get_username())).size(15),
+                                text(format!("Plan: {}", tier)).size(13),
+                            ]
+                        ].spacing(10).align_y(Center),
+                        text(free_warning).size(13).color(Color::from_rgb(255.0, 0.0, 0.0)),
+
+                        button("Screensaver settings").on_press(Message::OpenSettings),
 
                         row![
                             text("Build folder: ").size(15),
                             text(&self.build_status.0).size(15).color(build_red)
                         ].width(Length::Fixed(300.0)),
                         row![
-                            text_input("Build Directory", &self.build_dir.to_str().expect("Could not convert")),
+                            text_input("Build Directory", self.build_dir.to_str().expect("Could not convert")),
                             button("...").on_press(Message::SelectBuild)
                         ].width(Length::Fixed(300.0)),
 
@@ -111,7 +193,7 @@ impl LoginMenu {
                             text("Output folder: ").size(15),
                         ].width(Length::Fixed(300.0)),
                         row![
-                            text_input("Output Directory", &self.output_dir.to_str().expect("Could not convert")),
+                            text_input("Output Directory", self.output_dir.to_str().expect("Could not convert")),
                             button("...").on_press(Message::SelectOutput)
                         ].width(Length::Fixed(300.0)),
 
@@ -124,21 +206,109 @@ impl LoginMenu {
                 .align_y(Center)
                 .padding(10)
             }
+            Status::Settings => {
+                let saver = &self.settings.saver;
+                let albums_warning = if saver.idle == IdleScene::Albums && !self.grants(Scope::LIBRARY) {
+                    "Sign in with saved albums enabled to show them"
+                } else {
+                    ""
+                };
+                let controls_warning = if saver.interactive && !self.grants(Scope::MODIFY_PLAYBACK) {
+                    "Sign in with playback controls allowed to use them"
+                } else {
+                    ""
+                };
+                // The macOS saver only reads the size, speed and polling interval, so only those are offered there
+                let overlays = column![
+                    checkbox("Show track details", saver.info.show).on_toggle(Message::ToggleInfo).size(14).text_size(13),
+                    checkbox("Show progress bar", saver.progress_bar).on_toggle(Message::ToggleProgressBar).size(14).text_size(13),
+                    checkbox("Show elapsed and remaining time", saver.progress_times).on_toggle(Message::ToggleProgressTimes).size(14).text_size(13),
+                    checkbox("Show the next corner hit", saver.predict_corners).on_toggle(Message::TogglePredictCorners).size(14).text_size(13),
+                    checkbox("Leave a trail", saver.trail).on_toggle(Message::ToggleTrail).size(14).text_size(13),
+                    checkbox("Show which device is playing", saver.show_device).on_toggle(Message::ToggleShowDevice).size(14).text_size(13),
+                    row![
+                        text("Transition: ").size(13),
+                        pick_list(TransitionStyle::ALL, Some(saver.transition.style), Message::SelectTransition).text_size(13)
+                    ].align_y(Center),
+                    text(format!("Transition length: {} ms", saver.transition.duration_ms)).size(13),
+                    slider(100..=2000, saver.transition.duration_ms as u32, Message::SetTransitionDuration).step(50u32),
+                    row![
+                        text("When nothing plays: ").size(13),
+                        pick_list(IdleScene::ALL, Some(saver.idle), Message::SelectIdle).text_size(13)
+                    ].align_y(Center),
+                    text(albums_warning).size(13).color(Color::from_rgb(255.0, 0.0, 0.0)),
+                ].spacing(6);
+                let display = column![
+                    row![
+                        text("Colors: ").size(13),
+                        pick_list(Tint::ALL, Some(saver.tint), Message::SelectTint).text_size(13)
+                    ].align_y(Center),
+                    row![
+                        text("Displays: ").size(13),
+                        pick_list(DisplayMode::ALL, Some(saver.displays), Message::SelectDisplays).text_size(13)
+                    ].align_y(Center),
+                    checkbox("Control playback with space, arrows and media keys", saver.interactive)
+                        .on_toggle(Message::ToggleInteractive).size(14).text_size(13),
+                    text(controls_warning).size(13).color(Color::from_rgb(255.0, 0.0, 0.0)),
+                ].spacing(6);
+                container(scrollable(
+                    column![
+                        Canvas::new(&self.preview).width(Length::Fixed(320.0)).height(Length::Fixed(180.0)),
+                        text(format!("Cover size: {:.0}% of the screen", saver.size * 100.0)).size(13),
+                        slider(0.1..=0.6, saver.size, Message::SetSize).step(0.01),
+                        text(format!("Speed: {:.0} pixels per second", saver.speed)).size(13),
+                        slider(50.0..=500.0, saver.speed, Message::SetSpeed).step(10.0),
+                    ]
+                    .spacing(6)
+                    .push_maybe((!BASIC_SAVER).then_some(overlays))
+                    .push(text(format!("Check playback every {:.1} s", saver.poll_interval_ms as f32 / 1000.0)).size(13))
+                    .push(slider(1000..=10000, saver.poll_interval_ms as u32, Message::SetPollInterval).step(500u32))
+                    .push_maybe((!BASIC_SAVER).then_some(display))
+                    .push(button("Done").on_press(Message::CloseSettings))
+                    .width(Length::Fixed(320.0))
+                ))
+                .height(Length::Fill)
+                .width(Length::Fill)
+                .align_x(Center)
+                .align_y(Center)
+                .padding(10)
+            }
             Status::SignIn => {
+                let scope = Scope::for_features(&self.features);
+                let permissions = format!("Permissions requested:\n{}", scope.descriptions().join("\n"));
+                // Warn when a newly enabled feature needs a scope the saved login was not granted
+                let missing = match self.stored_scope {
+                    Some(stored) if !stored.contains(scope) => format!(
+                        "Your saved login is missing: {}",
+                        stored.missing(scope).descriptions().join(", ").to_lowercase()
+                    ),
+                    _ => String::new(),
+                };
                 container(
                     column![
                         text_input("Client ID", &self.id_input)
                         .width(250)
-                        .on_input(|value| Message::InputID(value)),
+                        .on_input(Message::InputID),
                         text_input("Client Secret", &self.secret_input)
                             .width(250)
-                            .on_input(|value| Message::InputSecret(value))
+                            .on_input(Message::InputSecret)
                             .on_submit(Message::ToSelection),
+                        column![
+                            checkbox("Show now playing", self.features.playback),
+                            checkbox("Show recently played when idle", self.features.recently_played)
+                                .on_toggle(Message::ToggleRecentlyPlayed),
+                            checkbox("Show saved albums when idle", self.features.library)
+                                .on_toggle(Message::ToggleLibrary),
+                            checkbox("Allow playback controls", self.features.playback_control)
+                                .on_toggle(Message::TogglePlaybackControl),
+                        ].spacing(5).width(250),
+                        text(permissions).size(13),
+                        text(missing).size(13).color(Color::from_rgb(255.0, 0.0, 0.0)),
                         row![
                             button("Submit").on_press(Message::ToSelection)
                         ].spacing(30),
                         text(&self.sign_in_message).color(Color::from_rgb(255.0, 0.0, 0.0))
-                    ].align_x(Center)
+                    ].align_x(Center).spacing(10)
                 )
                     .height(Length::Fill)
                     .width(Length::Fill)
@@ -169,18 +339,34 @@ impl LoginMenu {
     }
 
 
+    /// Whether the login being made, or the one saved before, allows `scope`
+    fn grants(&self, scope: Scope) -> bool {
+        Scope::for_features(&self.features).contains(scope) || self.stored_scope.is_some_and(|stored| stored.contains(scope))
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        // Only the settings page shows the preview
+        match self.content {
+            Status::Settings => window::frames().map(Message::PreviewTick),
+            _ => Subscription::none(),
+        }
+    }
+
     fn update(&mut self, message: Message) {
         match message {
             Message::NextPage => {
                 if self.build_dir.exists() {
+                    self.settings.save().expect("Could not write settings.json");
                     let user_file = "user.json";
                     let constants_file = "constants.json";
                     let mut user_path = self.build_dir.clone();
                     user_path.push("SpotifyScreensaver/user.json");
                     let mut constants_path = self.build_dir.clone();
                     constants_path.push("SpotifyScreensaver/constants.json");
+                    let settings_path = self.build_dir.join("SpotifyScreensaver").join(SETTINGS_FILE);
                     fs::copy(user_file,user_path).expect("Unable to copy file to resources");
                     fs::copy(constants_file,constants_path).expect("Unable to copy file to resources");
+                    fs::copy(SETTINGS_FILE, settings_path).expect("Unable to copy file to resources");
 
                     Command::new("xcodebuild").current_dir(&self.build_dir).arg("build").output().expect("Could not build");
                     
@@ -201,16 +387,93 @@ impl LoginMenu {
             Message::InputSecret(value) => {
                 self.secret_input = value;
             }
+            Message::ToggleRecentlyPlayed(value) => {
+                self.features.recently_played = value;
+            }
+            Message::ToggleLibrary(value) => {
+                // The idle scene is chosen in the settings, which warn while it cannot be shown
+                self.features.library = value;
+            }
+            Message::TogglePlaybackControl(value) => {
+                // The interactive mode is turned on in the settings, which warn while it cannot work
+                self.features.playback_control = value;
+            }
+            Message::OpenSettings => {
+                self.content = Status::Settings;
+            }
+            Message::CloseSettings => {
+                self.content = Status::UserSelect;
+            }
+            Message::SetSize(size) => {
+                self.settings.saver.size = size;
+            }
+            Message::SetSpeed(speed) => {
+                self.settings.saver.speed = speed;
+            }
+            Message::ToggleInfo(value) => {
+                self.settings.saver.info.show = value;
+            }
+            Message::ToggleProgressBar(value) => {
+                self.settings.saver.progress_bar = value;
+            }
+            Message::ToggleProgressTimes(value) => {
+                self.settings.saver.progress_times = value;
+            }
+            Message::TogglePredictCorners(value) => {
+                self.settings.saver.predict_corners = value;
+            }
+            Message::ToggleTrail(value) => {
+                self.settings.saver.trail = value;
+            }
+            Message::ToggleInteractive(value) => {
+                self.settings.saver.interactive = value;
+            }
+            Message::ToggleShowDevice(value) => {
+                self.settings.saver.show_device = value;
+            }
+            Message::SelectTransition(style) => {
+                self.settings.saver.transition.style = style;
+            }
+            Message::SetTransitionDuration(duration_ms) => {
+                self.settings.saver.transition.duration_ms = duration_ms as u64;
+            }
+            Message::SelectIdle(scene) => {
+                self.settings.saver.idle = scene;
+            }
+            Message::SetPollInterval(interval_ms) => {
+                self.settings.saver.poll_interval_ms = interval_ms as u64;
+            }
+            Message::SelectTint(tint) => {
+                self.settings.saver.tint = tint;
+            }
+            Message::SelectDisplays(mode) => {
+                self.settings.saver.displays = mode;
+            }
+            Message::PreviewTick(now) => {
+                self.preview.tick(now);
+                return;
+            }
             Message::ToSelection => {
                 self.client.set_id(&self.id_input);
                 self.client.set_secret(&self.secret_input);
+                self.client.set_scope(Scope::for_features(&self.features));
                 if !(self.id_input.is_empty() || self.secret_input.is_empty()) && self.client.generate_refresh(){
                     self.client.generate_token();
-                    self.client.set_username();
-                    self.content = Status::UserSelect;
+                    match self.client.load_profile() {
+                        Ok(()) => {
+                            self.avatar = self.client.avatar().map(|bytes| image::Handle::from_bytes(bytes.to_vec()));
+                            if let Some((item, bytes)) = self.client.now_playing(PREVIEW_ART_SIDE) {
+                                self.preview.set_cover(&bytes, &item);
+                            }
+                            self.content = Status::UserSelect;
+                        }
+                        Err(e) => {
+                            self.sign_in_message = format!("Could not load account: {}", e);
+                        }
+                    }
                 } else {
                     let error = if self.id_input.is_empty() || self.secret_input.is_empty() {"empty client or secret"} else {"timed out"};
-                    self.sign_in_message = String::from(format!("{}, please try again", error));
+                    self.sign_in_message = format!("{}, please try again", error);
                 }
             }
             Message::CloseWindow => {
@@ -218,10 +481,9 @@ impl LoginMenu {
             }
             Message::SelectBuild => {
                 let destination = FileDialog::new().pick_folder();
-                if destination.is_some() {
-                    let path = destination.as_ref().unwrap();
-                    if containts_valid(path) {
-                        self.build_dir = destination.unwrap();
+                if let Some(path) = destination {
+                    if containts_valid(&path) {
+                        self.build_dir = path;
                         self.build_status = (String::from("valid directory"), true);
                     } else {
                         self.build_status = (String::from("invalid directory"), false);
@@ -232,31 +494,235 @@ impl LoginMenu {
             }
             Message::SelectOutput => {
                 let destination = FileDialog::new().pick_folder();
-                if destination.is_some() {
-                    self.output_dir = destination.unwrap();
+                if let Some(path) = destination {
+                    self.output_dir = path;
                     self.output_status = (String::from("valid directory"), true);
                 } else {
                     self.output_status = (String::from("please select a valid folder"), false);
                 }
             }
         }
+        // Every other message may have changed a setting
+        self.preview.apply(&previewed(&self.settings.saver));
     }
 }
 
 
 fn main() -> iced::Result {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        // Windows passes /s when the screensaver should start
+        Some("saver") | Some("/s") => return renderer::run(source_arg(&args)),
+        Some("watch") => {
+            let runtime = tokio::runtime::Runtime::new().expect("Could not start runtime");
+            runtime.block_on(async {
+                let settings = Settings::load();
+                let history = HistoryLogger::from_settings(&settings.history, HistoryWriter::Watch);
+                match source_arg(&args).connect(None).await {
+                    Ok(source) => events::watch(source, Hooks::new(settings.hooks), history).await,
+                    Err(e) => println!("Could not connect to playback source: {e}"),
+                }
+            });
+            return Ok(());
+        }
+        Some("serve") => {
+            let port = match args.iter().position(|arg| arg == "--port") {
+                Some(index) => args
+                    .get(index + 1)
+                    .and_then(|value| value.parse().ok())
+                    .expect("Expected --port <number>"),
+                None => server::DEFAULT_PORT,
+            };
+            let runtime = tokio::runtime::Runtime::new().expect("Could not start runtime");
+            runtime.block_on(async {
+                let settings = Settings::load();
+                let history = HistoryLogger::from_settings(&settings.history, HistoryWriter::Serve);
+                match source_arg(&args).connect(None).await {
+                    Ok(source) => server::serve(source, port, settings.server, Hooks::new(settings.hooks), history).await,
+                    Err(e) => println!("Could not connect to playback source: {e}"),
+                }
+            });
+            return Ok(());
+        }
+        Some("history") => {
+            history_command(&args);
+            return Ok(());
+        }
+        Some("control") => {
+            control_command(&args);
+            return Ok(());
+        }
+        Some("devices") => {
+            let runtime = tokio::runtime::Runtime::new().expect("Could not start runtime");
+            runtime.block_on(async {
+                let devices = match SpotifySource::connect(SpotifyUser::granted_scope(), None).await {
+                    Ok(mut source) => source.devices().await,
+                    Err(e) => Err(e),
+                };
+                match devices {
+                    Ok(devices) => print_devices(&devices),
+                    Err(e) => println!("Could not list devices: {e}"),
+                }
+            });
+            return Ok(());
+        }
+        _ => {}
+    }
+
     let window_settings = window::Settings {
-        size: iced::Size { width: 450.0, height: 200.0},
+        size: iced::Size { width: 450.0, height: 420.0},
         resizable: true, 
         ..Default::default()
     };
     let app = iced
         ::application(LoginMenu::title, LoginMenu::update, LoginMenu::view)
+        .subscription(LoginMenu::subscription)
         .window(window_settings);
     app.run_with(LoginMenu::new)
 }
 
 
+/// Reads the optional `--source <kind>` argument, defaulting to the Web API
+fn source_arg(args: &[String]) -> SourceKind {
+    match args.iter().position(|arg| arg == "--source") {
+        Some(index) => args
+            .get(index + 1)
+            .and_then(|value| SourceKind::parse(value))
+            .expect("Expected --source spotify, mpris, mpris:<player> or mock:<file>"),
+        None => SourceKind::Spotify,
+    }
+}
+
+/// Reads the optional `<name> <value>` argument
+fn flag<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .map(|index| args.get(index + 1).map(String::as_str).unwrap_or_else(|| panic!("Expected a value after {name}")))
+}
+
+/// `settings` as the saver being built draws them
+///
+/// The macOS saver only reads the size, speed and poll interval, so its preview turns off every
+/// overlay, the tint, the corner celebration and the cover transition
+fn previewed(settings: &SaverSettings) -> SaverSettings {
+    if !BASIC_SAVER {
+        return settings.clone();
+    }
+    SaverSettings {
+        info: InfoSettings { show: false, ..settings.info.clone() },
+        progress_bar: false,
+        progress_times: false,
+        trail: false,
+        tint: Tint::None,
+        celebration: Celebration::None,
+        predict_corners: false,
+        show_device: false,
+        transition: TransitionSettings { style: TransitionStyle::None, ..settings.transition.clone() },
+        ..settings.clone()
+    }
+}
+
+/// Runs `history export` or `history stats` over the logged history
+fn history_command(args: &[String]) {
+    let from = flag(args, "--from").map(|value| history::parse_bound(value, false).expect("Expected --from YYYY-MM-DD"));
+    let to = flag(args, "--to").map(|value| history::parse_bound(value, true).expect("Expected --to YYYY-MM-DD"));
+    let settings = Settings::load().history;
+    let records = match history::read(&settings, from, to) {
+        Ok(records) => records,
+        Err(e) => {
+            println!("Could not read {}: {e}", settings.path.display());
+            return;
+        }
+    };
+
+    match args.get(2).map(String::as_str) {
+        Some("export") => {
+            let format = flag(args, "--format")
+                .map(|value| history::Format::parse(value).expect("Expected --format csv or json"))
+                .unwrap_or(history::Format::Csv);
+            if let Err(e) = history::export(&records, format) {
+                println!("Could not export history: {e}");
+            }
+        }
+        Some("stats") => {
+            let top = flag(args, "--top").map(|value| value.parse().expect("Expected --top <number>")).unwrap_or(10);
+            history::print_stats(&records, top);
+        }
+        _ => println!("Expected history export [--from] [--to] [--format csv|json] or history stats [--from] [--to] [--top]"),
+    }
+}
+
+/// Prints one line per device, with the ID `control --device` takes
+fn print_devices(devices: &[Device]) {
+    if devices.is_empty() {
+        println!("No devices are available, open Spotify on one first");
+    }
+    for device in devices {
+        let volume = device.volume_percent.map(|percent| format!(", volume {}%", percent)).unwrap_or_default();
+        let active = if device.is_active { ", playing" } else { "" };
+        println!(
+            "{}  {} ({}{}{})",
+            device.id.as_deref().unwrap_or("<no id>"),
+            device.name,
+            device.kind,
+            volume,
+            active,
+        );
+    }
+}
+
+/// Reads the playback command following `control`
+fn parse_control(args: &[String]) -> Option<PlaybackCommand> {
+    let value = args.get(3).map(String::as_str);
+    match args.get(2).map(String::as_str)? {
+        "play" => Some(PlaybackCommand::Play),
+        "pause" => Some(PlaybackCommand::Pause),
+        "next" => Some(PlaybackCommand::Next),
+        "previous" => Some(PlaybackCommand::Previous),
+        "seek" => value?.parse::<u64>().ok().map(|seconds| PlaybackCommand::Seek(seconds * 1000)),
+        "volume" => value?.parse().ok().map(PlaybackCommand::Volume),
+        "shuffle" => match value? {
+            "on" => Some(PlaybackCommand::Shuffle(true)),
+            "off" => Some(PlaybackCommand::Shuffle(false)),
+            _ => None,
+        },
+        "repeat" => RepeatMode::parse(value?).map(PlaybackCommand::Repeat),
+        "transfer" => Some(PlaybackCommand::Transfer { device: value?.to_string(), play: args.iter().any(|arg| arg == "--play") }),
+        "queue" => Some(PlaybackCommand::AddToQueue(value?.to_string())),
+        _ => None,
+    }
+}
+
+/// Runs one `control` command, on the device given by `--device` if set
+fn control_command(args: &[String]) {
+    let Some(command) = parse_control(args) else {
+        println!("Expected control play, pause, next, previous, seek <seconds>, volume <percent>, shuffle on|off, repeat track|context|off, transfer <device id> [--play] or queue <uri>");
+        return;
+    };
+    let device = flag(args, "--device");
+    let runtime = tokio::runtime::Runtime::new().expect("Could not start runtime");
+    runtime.block_on(async {
+        let result = match (source_arg(args), device) {
+            (SourceKind::Spotify, device) => match SpotifySource::connect(SpotifyUser::granted_scope(), None).await {
+                Ok(mut source) => source.control_device(&command, device).await,
+                Err(e) => Err(e),
+            },
+            (_, Some(_)) => {
+                println!("--device only works with the Spotify Web API");
+                return;
+            }
+            (kind, None) => match kind.connect(None).await {
+                Ok(mut source) => source.control(command.clone()).await,
+                Err(e) => Err(e),
+            },
+        };
+        match result {
+            Ok(()) => println!("{}", command.done()),
+            Err(e) => println!("Could not {}: {e}", command.action()),
+        }
+    });
+}
+
 fn copy_dir(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result<()> {
     fs::create_dir_all(&dst).expect("Cannot Create Directory");
     for entry in fs::read_dir(src)? {
@@ -272,5 +738,5 @@ fn copy_dir(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result<()>
 }
 
 fn containts_valid(path: &Path) -> bool {
-    return path.exists() && path.join(Path::new("SpotifyScreensaver.xcodeproj")).exists()
+    path.exists() && path.join(Path::new("SpotifyScreensaver.xcodeproj")).exists()
 }
\ No newline at end of file
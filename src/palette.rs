@@ -0,0 +1,185 @@
+use iced::Color;
+
+/// Side covers are shrunk to before sampling, plenty for picking a few colors
+const SAMPLE_SIDE: u32 = 64;
+/// How many boxes median cut splits the colors into
+const BOXES: usize = 8;
+/// Passes of k-means refining the median cut colors
+const ITERATIONS: usize = 4;
+/// Colors less saturated than this are not picked as the accent
+const MIN_ACCENT_SATURATION: f32 = 0.2;
+
+/// Represents the colors picked from a cover
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    /// Color covering most of the cover
+    pub dominant: Color,
+    /// Most vivid color that still covers a fair part of the cover
+    pub accent: Color,
+}
+
+impl Palette {
+    /// Colors used while no cover has been read yet
+    pub const PLAIN: Palette = Palette {
+        dominant: Color::BLACK,
+        accent: Color::WHITE,
+    };
+
+    /// Picks the colors of a decoded cover, `None` if it has no pixels
+    ///
+    /// Median cut finds the starting colors, then k-means moves them to where the pixels cluster
+    pub fn extract(image: &image::DynamicImage) -> Option<Palette> {
+        let pixels: Vec<[u8; 3]> = image
+            .thumbnail(SAMPLE_SIDE, SAMPLE_SIDE)
+            .to_rgb8()
+            .pixels()
+            .map(|pixel| pixel.0)
+            .collect();
+        if pixels.is_empty() {
+            return None;
+        }
+
+        let centers = median_cut(pixels.clone(), BOXES);
+        let mut boxes = k_means(&pixels, centers);
+        boxes.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let dominant = boxes[0].0;
+        // Weighing by the square root of the population lets a smaller vivid box beat a large dull one
+        let accent = boxes
+            .iter()
+            .skip(1)
+            .filter(|(color, _)| saturation(*color) >= MIN_ACCENT_SATURATION)
+            .max_by(|(a, a_count), (b, b_count)| {
+                let a_score = saturation(*a) * a.r.max(a.g).max(a.b) * (*a_count as f32).sqrt();
+                let b_score = saturation(*b) * b.r.max(b.g).max(b.b) * (*b_count as f32).sqrt();
+                a_score.total_cmp(&b_score)
+            })
+            .map_or_else(|| mix(dominant, Color::WHITE, 0.6), |(color, _)| *color);
+        Some(Palette { dominant, accent })
+    }
+
+    /// The dominant color darkened enough to sit behind the cover
+    pub fn background(&self) -> Color {
+        mix(self.dominant, Color::BLACK, 0.65)
+    }
+
+    /// The accent lightened enough to read on the background
+    pub fn highlight(&self) -> Color {
+        mix(self.accent, Color::WHITE, 0.35)
+    }
+
+    /// Blends from `self` to `other`, `amount` going from 0 to 1
+    pub fn lerp(&self, other: &Palette, amount: f32) -> Palette {
+        Palette {
+            dominant: mix(self.dominant, other.dominant, amount),
+            accent: mix(self.accent, other.accent, amount),
+        }
+    }
+}
+
+/// Splits `pixels` into at most `count` boxes of equal size, returning each box's average color
+fn median_cut(pixels: Vec<[u8; 3]>, count: usize) -> Vec<[f32; 3]> {
+    let mut boxes = vec![pixels];
+    while boxes.len() < count {
+        // Split the box whose colors spread the furthest along any channel
+        let Some((index, channel)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, pixels)| pixels.len() > 1)
+            .map(|(index, pixels)| {
+                let (channel, range) = (0..3)
+                    .map(|channel| {
+                        let (low, high) = pixels.iter().fold((u8::MAX, u8::MIN), |(low, high), pixel| {
+                            (low.min(pixel[channel]), high.max(pixel[channel]))
+                        });
+                        (channel, high - low)
+                    })
+                    .max_by_key(|(_, range)| *range)
+                    .expect("Three channels");
+                (index, channel, range)
+            })
+            .filter(|(_, _, range)| *range > 0)
+            .max_by_key(|(_, _, range)| *range)
+            .map(|(index, channel, _)| (index, channel))
+        else {
+            break;
+        };
+
+        let mut pixels = boxes.swap_remove(index);
+        pixels.sort_unstable_by_key(|pixel| pixel[channel]);
+        let upper = pixels.split_off(pixels.len() / 2);
+        boxes.push(pixels);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|pixels| average(pixels.iter())).collect()
+}
+
+/// Moves each center to the average of the pixels closest to it, returning the centers and how many pixels each has
+fn k_means(pixels: &[[u8; 3]], mut centers: Vec<[f32; 3]>) -> Vec<(Color, usize)> {
+    let mut nearest = vec![0; pixels.len()];
+    for _ in 0..ITERATIONS {
+        for (pixel, nearest) in pixels.iter().zip(nearest.iter_mut()) {
+            *nearest = (0..centers.len())
+                .min_by(|a, b| distance(pixel, &centers[*a]).total_cmp(&distance(pixel, &centers[*b])))
+                .expect("At least one center");
+        }
+        for (index, center) in centers.iter_mut().enumerate() {
+            let mut members = pixels.iter().zip(&nearest).filter(|(_, nearest)| **nearest == index).peekable();
+            // A center nobody is closest to stays put and ends up with no pixels
+            if members.peek().is_some() {
+                *center = average(members.map(|(pixel, _)| pixel));
+            }
+        }
+    }
+    centers
+        .iter()
+        .enumerate()
+        .map(|(index, center)| {
+            let count = nearest.iter().filter(|nearest| **nearest == index).count();
+            (Color::from_rgb(center[0] / 255.0, center[1] / 255.0, center[2] / 255.0), count)
+        })
+        .collect()
+}
+
+fn average<'a>(pixels: impl Iterator<Item = &'a [u8; 3]>) -> [f32; 3] {
+    let (sums, count) = pixels.fold(([0u64; 3], 0u64), |(sums, count), pixel| {
+        ([sums[0] + pixel[0] as u64, sums[1] + pixel[1] as u64, sums[2] + pixel[2] as u64], count + 1)
+    });
+    let count = count.max(1) as f32;
+    [sums[0] as f32 / count, sums[1] as f32 / count, sums[2] as f32 / count]
+}
+
+fn distance(pixel: &[u8; 3], center: &[f32; 3]) -> f32 {
+    (0..3).map(|channel| (pixel[channel] as f32 - center[channel]).powi(2)).sum()
+}
+
+fn saturation(color: Color) -> f32 {
+    let high = color.r.max(color.g).max(color.b);
+    let low = color.r.min(color.g).min(color.b);
+    if high == 0.0 { 0.0 } else { (high - low) / high }
+}
+
+/// Builds a color from a hue going from 0 to 1 around the color wheel, wrapping past 1
+pub fn hsv(hue: f32, saturation: f32, value: f32) -> Color {
+    let hue = hue.rem_euclid(1.0) * 6.0;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let low = value - chroma;
+    Color::from_rgb(r + low, g + low, b + low)
+}
+
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    Color::from_rgb(
+        from.r + (to.r - from.r) * amount,
+        from.g + (to.g - from.g) * amount,
+        from.b + (to.b - from.b) * amount,
+    )
+}
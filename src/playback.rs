@@ -0,0 +1,525 @@
+use serde::{ Serialize, Deserialize };
+
+use std::fmt;
+
+/// Represents a response from the player endpoint
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlaybackState {
+    /// Currently playing object, missing while an ad or unknown item plays
+    pub item: Option<PlayingItem>,
+    #[serde(default)]
+    pub is_playing: bool,
+    /// Position in the item when the response was sent
+    pub progress_ms: Option<u64>,
+    /// Device the item is playing on
+    pub device: Option<Device>,
+}
+
+/// Represents a device that can play music
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Device {
+    /// Device ID, missing for some restricted devices
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub kind: DeviceType,
+    /// Whether this is the device playback is on
+    #[serde(default)]
+    pub is_active: bool,
+    #[serde(default)]
+    pub is_private_session: bool,
+    /// Restricted devices cannot be controlled through the Web API
+    #[serde(default)]
+    pub is_restricted: bool,
+    /// Missing for devices without volume control
+    pub volume_percent: Option<u8>,
+}
+
+/// Represents the kind of device playback is on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(from = "String", into = "String")]
+pub enum DeviceType {
+    Computer,
+    Tablet,
+    Smartphone,
+    Smartwatch,
+    Speaker,
+    Tv,
+    /// Audio/video receiver
+    Avr,
+    /// Set-top box
+    Stb,
+    AudioDongle,
+    GameConsole,
+    CastVideo,
+    CastAudio,
+    Automobile,
+    #[default]
+    Unknown,
+}
+
+impl From<String> for DeviceType {
+    /// Spotify sends the type capitalized, e.g. `Smartphone`, but is not consistent about it
+    fn from(value: String) -> Self {
+        match value.to_lowercase().as_str() {
+            "computer" => DeviceType::Computer,
+            "tablet" => DeviceType::Tablet,
+            "smartphone" => DeviceType::Smartphone,
+            "smartwatch" => DeviceType::Smartwatch,
+            "speaker" => DeviceType::Speaker,
+            "tv" => DeviceType::Tv,
+            "avr" => DeviceType::Avr,
+            "stb" => DeviceType::Stb,
+            "audiodongle" => DeviceType::AudioDongle,
+            "gameconsole" => DeviceType::GameConsole,
+            "castvideo" => DeviceType::CastVideo,
+            "castaudio" => DeviceType::CastAudio,
+            "automobile" => DeviceType::Automobile,
+            _ => DeviceType::Unknown,
+        }
+    }
+}
+
+impl From<DeviceType> for String {
+    /// Written the way Spotify sends it
+    fn from(kind: DeviceType) -> Self {
+        String::from(match kind {
+            DeviceType::Computer => "Computer",
+            DeviceType::Tablet => "Tablet",
+            DeviceType::Smartphone => "Smartphone",
+            DeviceType::Smartwatch => "Smartwatch",
+            DeviceType::Speaker => "Speaker",
+            DeviceType::Tv => "TV",
+            DeviceType::Avr => "AVR",
+            DeviceType::Stb => "STB",
+            DeviceType::AudioDongle => "AudioDongle",
+            DeviceType::GameConsole => "GameConsole",
+            DeviceType::CastVideo => "CastVideo",
+            DeviceType::CastAudio => "CastAudio",
+            DeviceType::Automobile => "Automobile",
+            DeviceType::Unknown => "Unknown",
+        })
+    }
+}
+
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DeviceType::Computer => "Computer",
+            DeviceType::Tablet => "Tablet",
+            DeviceType::Smartphone => "Phone",
+            DeviceType::Smartwatch => "Watch",
+            DeviceType::Speaker => "Speaker",
+            DeviceType::Tv => "TV",
+            DeviceType::Avr => "Receiver",
+            DeviceType::Stb => "Set-top box",
+            DeviceType::AudioDongle => "Audio dongle",
+            DeviceType::GameConsole => "Game console",
+            DeviceType::CastVideo => "Cast video",
+            DeviceType::CastAudio => "Cast audio",
+            DeviceType::Automobile => "Car",
+            DeviceType::Unknown => "Unknown",
+        })
+    }
+}
+
+/// Represents a response from the devices endpoint
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Devices {
+    pub devices: Vec<Device>,
+}
+
+/// Represents the object that is currently playing
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PlayingItem {
+    Track(Track),
+    Episode(Episode),
+    /// A chapter of an audiobook
+    Chapter(Chapter),
+    /// Any type that the screensaver does not know how to draw
+    #[serde(other)]
+    Unsupported,
+}
+
+/// Represents a music track
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Track {
+    /// Spotify URI, e.g. `spotify:track:<id>`, empty when unknown
+    #[serde(default)]
+    pub uri: String,
+    pub name: String,
+    pub artists: Vec<Artist>,
+    pub album: Album,
+    /// Length of the track, 0 when unknown
+    #[serde(default)]
+    pub duration_ms: u64,
+    /// Whether the track is a local file added to Spotify, which has no artwork
+    #[serde(default)]
+    pub is_local: bool,
+}
+
+/// Represents a podcast episode
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Episode {
+    /// Spotify URI, e.g. `spotify:episode:<id>`, empty when unknown
+    #[serde(default)]
+    pub uri: String,
+    pub name: String,
+    pub show: Show,
+    /// Artwork of the episode itself, which may differ from the show's
+    #[serde(default)]
+    pub images: Vec<Image>,
+    /// Length of the episode, 0 when unknown
+    #[serde(default)]
+    pub duration_ms: u64,
+}
+
+/// Represents the podcast an episode belongs to
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Show {
+    pub name: String,
+    #[serde(default)]
+    pub publisher: String,
+    #[serde(default)]
+    pub images: Vec<Image>,
+}
+
+/// Represents a chapter of an audiobook
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Chapter {
+    /// Spotify URI, e.g. `spotify:chapter:<id>`, empty when unknown
+    #[serde(default)]
+    pub uri: String,
+    pub name: String,
+    pub audiobook: Audiobook,
+    /// Artwork of the chapter itself, usually missing
+    #[serde(default)]
+    pub images: Vec<Image>,
+    /// Length of the chapter, 0 when unknown
+    #[serde(default)]
+    pub duration_ms: u64,
+}
+
+/// Represents the audiobook a chapter belongs to
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Audiobook {
+    pub name: String,
+    #[serde(default)]
+    pub authors: Vec<Author>,
+    #[serde(default)]
+    pub narrators: Vec<Author>,
+    #[serde(default)]
+    pub publisher: String,
+    #[serde(default)]
+    pub images: Vec<Image>,
+}
+
+/// Represents an author or narrator of an audiobook
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Author {
+    pub name: String,
+}
+
+/// Represents the album a track belongs to
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Album {
+    pub name: String,
+    pub images: Vec<Image>,
+    /// Missing from some local players
+    #[serde(default)]
+    pub artists: Vec<Artist>,
+}
+
+/// Represents a track artist
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Artist {
+    pub name: String,
+}
+
+/// Represents a piece of artwork hosted by Spotify
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Image {
+    pub url: String,
+    /// Width in pixels, unknown for some user uploaded images
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// Represents what repeats once the playing item ends
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatMode {
+    /// The playing item plays again
+    Track,
+    /// The album, playlist or show plays again from the start
+    Context,
+    Off,
+}
+
+impl RepeatMode {
+    pub fn parse(value: &str) -> Option<RepeatMode> {
+        match value {
+            "track" => Some(RepeatMode::Track),
+            "context" => Some(RepeatMode::Context),
+            "off" => Some(RepeatMode::Off),
+            _ => None,
+        }
+    }
+
+    /// Name used by the Web API
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RepeatMode::Track => "track",
+            RepeatMode::Context => "context",
+            RepeatMode::Off => "off",
+        }
+    }
+}
+
+/// Represents a change to playback asked for by the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlaybackCommand {
+    Play,
+    Pause,
+    Next,
+    Previous,
+    /// Jumps to a position in the playing item, in milliseconds
+    Seek(u64),
+    /// Sets the volume in percent
+    Volume(u8),
+    Shuffle(bool),
+    Repeat(RepeatMode),
+    /// Moves playback to the device with this ID, starting it if `play` is set
+    Transfer { device: String, play: bool },
+    /// Adds the track or episode with this URI to the end of the queue
+    AddToQueue(String),
+}
+
+impl PlaybackCommand {
+    /// Confirms the command was carried out, e.g. "Paused"
+    pub fn done(&self) -> String {
+        match self {
+            PlaybackCommand::Play => String::from("Playing"),
+            PlaybackCommand::Pause => String::from("Paused"),
+            PlaybackCommand::Next => String::from("Next track"),
+            PlaybackCommand::Previous => String::from("Previous track"),
+            PlaybackCommand::Seek(position_ms) => format!("Jumped to {}", clock(*position_ms)),
+            PlaybackCommand::Volume(percent) => format!("Volume {}%", percent),
+            PlaybackCommand::Shuffle(true) => String::from("Shuffle on"),
+            PlaybackCommand::Shuffle(false) => String::from("Shuffle off"),
+            PlaybackCommand::Repeat(mode) => format!("Repeat {}", mode.as_str()),
+            PlaybackCommand::Transfer { .. } => String::from("Playback moved"),
+            PlaybackCommand::AddToQueue(_) => String::from("Added to the queue"),
+        }
+    }
+
+    /// What the command does, finishing "Could not ..."
+    pub fn action(&self) -> &'static str {
+        match self {
+            PlaybackCommand::Play => "play",
+            PlaybackCommand::Pause => "pause",
+            PlaybackCommand::Next => "skip",
+            PlaybackCommand::Previous => "go back",
+            PlaybackCommand::Seek(_) => "seek",
+            PlaybackCommand::Volume(_) => "set the volume",
+            PlaybackCommand::Shuffle(_) => "change shuffle",
+            PlaybackCommand::Repeat(_) => "change repeat",
+            PlaybackCommand::Transfer { .. } => "move playback",
+            PlaybackCommand::AddToQueue(_) => "add to the queue",
+        }
+    }
+}
+
+/// Represents a response from the recently played endpoint
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecentlyPlayed {
+    pub items: Vec<PlayHistory>,
+}
+
+/// Represents a response from the queue endpoint
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Queue {
+    /// Items that will play next, in order
+    pub queue: Vec<PlayingItem>,
+}
+
+/// Represents a response from the saved albums endpoint
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedAlbums {
+    pub items: Vec<SavedAlbum>,
+}
+
+/// Represents an album in the user's library
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedAlbum {
+    /// ISO 8601 timestamp of when the album was saved
+    pub added_at: String,
+    pub album: Album,
+}
+
+/// Represents a single entry of the user's listening history
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayHistory {
+    pub track: Track,
+    /// ISO 8601 timestamp of when the track was played
+    pub played_at: String,
+}
+
+impl Track {
+    /// Artist names joined into a single line
+    pub fn artist_names(&self) -> String {
+        self.artists
+            .iter()
+            .map(|artist| artist.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ")
+    }
+}
+
+impl Album {
+    /// Artist names joined into a single line
+    pub fn artist_names(&self) -> String {
+        self.artists
+            .iter()
+            .map(|artist| artist.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ")
+    }
+}
+
+impl Image {
+    /// Picks the smallest image covering a square of `side` physical pixels
+    ///
+    /// Falls back to the largest image when none are big enough, and to `None` when the list is empty
+    pub fn best_fit(images: &[Image], side: u32) -> Option<&Image> {
+        // Images without a known width are only picked if nothing else is available
+        let width = |image: &Image| image.width.unwrap_or(0);
+        images
+            .iter()
+            .filter(|image| width(image) >= side)
+            .min_by_key(|image| width(image))
+            .or_else(|| images.iter().max_by_key(|image| width(image)))
+    }
+}
+
+/// Formats a position as `m:ss`
+pub fn clock(milliseconds: u64) -> String {
+    let seconds = milliseconds / 1000;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// `own` unless it is empty, `fallback` otherwise
+fn own_or<'a>(own: &'a [Image], fallback: &'a [Image]) -> &'a [Image] {
+    if own.is_empty() { fallback } else { own }
+}
+
+impl PlaybackState {
+    /// The playing item, unless it is one the screensaver cannot draw
+    pub fn drawable_item(&self) -> Option<&PlayingItem> {
+        self.item.as_ref().filter(|item| !matches!(item, PlayingItem::Unsupported))
+    }
+}
+
+impl PlayingItem {
+    /// Artwork to draw for the item, empty if it has none and the placeholder is drawn
+    ///
+    /// Episodes and chapters fall back to the show's or audiobook's artwork when they have none of their own
+    pub fn images(&self) -> &[Image] {
+        match self {
+            PlayingItem::Track(track) => &track.album.images,
+            PlayingItem::Episode(episode) => own_or(&episode.images, &episode.show.images),
+            PlayingItem::Chapter(chapter) => own_or(&chapter.images, &chapter.audiobook.images),
+            PlayingItem::Unsupported => &[],
+        }
+    }
+
+    /// Name of the kind of item: `track`, `local`, `episode` or `chapter`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PlayingItem::Track(track) if track.is_local => "local",
+            PlayingItem::Track(_) => "track",
+            PlayingItem::Episode(_) => "episode",
+            PlayingItem::Chapter(_) => "chapter",
+            PlayingItem::Unsupported => "unsupported",
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            PlayingItem::Track(track) => &track.name,
+            PlayingItem::Episode(episode) => &episode.name,
+            PlayingItem::Chapter(chapter) => &chapter.name,
+            PlayingItem::Unsupported => "",
+        }
+    }
+
+    /// Who made the item, joined with `separator`: the artists, the show or the authors
+    pub fn creators(&self, separator: &str) -> String {
+        let join = |names: Vec<&str>| names.join(separator);
+        match self {
+            PlayingItem::Track(track) => join(track.artists.iter().map(|artist| artist.name.as_str()).collect()),
+            PlayingItem::Episode(episode) => episode.show.name.clone(),
+            PlayingItem::Chapter(chapter) => join(chapter.audiobook.authors.iter().map(|author| author.name.as_str()).collect()),
+            PlayingItem::Unsupported => String::new(),
+        }
+    }
+
+    /// What the item belongs to: the album, show or audiobook
+    pub fn collection(&self) -> &str {
+        match self {
+            PlayingItem::Track(track) => &track.album.name,
+            PlayingItem::Episode(episode) => &episode.show.name,
+            PlayingItem::Chapter(chapter) => &chapter.audiobook.name,
+            PlayingItem::Unsupported => "",
+        }
+    }
+
+    /// Lines drawn with the item's artwork, leaving out any that are unknown
+    pub fn info(&self) -> Vec<String> {
+        let lines = match self {
+            PlayingItem::Track(track) if track.is_local => vec![track.name.clone(), track.artist_names(), String::from("Local file")],
+            PlayingItem::Track(track) => vec![track.name.clone(), track.artist_names(), track.album.name.clone()],
+            PlayingItem::Episode(episode) => vec![episode.name.clone(), episode.show.name.clone(), episode.show.publisher.clone()],
+            PlayingItem::Chapter(chapter) => {
+                let authors = self.creators(", ");
+                let by = if authors.is_empty() { authors } else { format!("by {}", authors) };
+                vec![chapter.name.clone(), chapter.audiobook.name.clone(), by]
+            }
+            PlayingItem::Unsupported => Vec::new(),
+        };
+        lines.into_iter().filter(|line| !line.is_empty()).collect()
+    }
+
+    /// Length of the item, 0 when unknown
+    pub fn duration_ms(&self) -> u64 {
+        match self {
+            PlayingItem::Track(track) => track.duration_ms,
+            PlayingItem::Episode(episode) => episode.duration_ms,
+            PlayingItem::Chapter(chapter) => chapter.duration_ms,
+            PlayingItem::Unsupported => 0,
+        }
+    }
+
+    /// Spotify URI of the item, empty when unknown
+    pub fn uri(&self) -> &str {
+        match self {
+            PlayingItem::Track(track) => &track.uri,
+            PlayingItem::Episode(episode) => &episode.uri,
+            PlayingItem::Chapter(chapter) => &chapter.uri,
+            PlayingItem::Unsupported => "",
+        }
+    }
+
+    /// Whether both items are the same track, episode or chapter
+    pub fn same_as(&self, other: &PlayingItem) -> bool {
+        if std::mem::discriminant(self) != std::mem::discriminant(other) {
+            return false;
+        }
+        // Local players may not report a URI
+        if !self.uri().is_empty() {
+            return self.uri() == other.uri();
+        }
+        self.name() == other.name() && self.collection() == other.collection()
+    }
+}
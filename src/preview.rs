@@ -0,0 +1,213 @@
+use iced::{mouse, widget::{canvas, image}, Color, Point, Rectangle, Renderer, Size, Theme};
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::bounce::BounceSim;
+use crate::palette::Palette;
+use crate::playback::{Device, DeviceType, PlayingItem};
+use crate::renderer::{self, CaptionStyle, Party, TRAIL_LENGTH, TRAIL_SPACING};
+use crate::settings::{SaverSettings, TransitionStyle};
+use crate::transition::CoverTransition;
+
+/// Screen the preview stands in for, the cover moves across it as it would on a real one
+const SCREEN: Size = Size::new(1920.0, 1080.0);
+const PLACEHOLDER: &[u8] = include_bytes!("../images/placeholder.jpg");
+/// Length of the made up track the progress bar plays through
+const SAMPLE_DURATION: Duration = Duration::from_secs(180);
+
+/// Represents a scaled down screensaver, drawn with the settings being edited
+pub struct Preview {
+    settings: SaverSettings,
+    bounce: BounceSim,
+    /// `None` if no text is drawn with the cover
+    caption_style: Option<CaptionStyle>,
+    /// Lines describing the cover, shortened to its width
+    caption: Vec<String>,
+    placeholder: image::Handle,
+    cover: image::Handle,
+    info: Vec<String>,
+    palette: Palette,
+    /// Recent top left corners of the square, oldest first
+    trail: VecDeque<Point>,
+    /// `None` unless the cover is changing
+    transition: Option<CoverTransition>,
+    /// Corners hit since the preview started, kept apart from the count in corners.json
+    hits: u64,
+    party: Option<Party>,
+    /// Stands in for the device playing, which the installer does not look up
+    device: Device,
+    started: Instant,
+    last_frame: Option<Instant>,
+}
+
+impl Preview {
+    /// Starts the preview on the placeholder art
+    pub fn new(settings: &SaverSettings) -> Preview {
+        let (cover, palette) = renderer::decode_cover(PLACEHOLDER).expect("Could not decode placeholder");
+        let mut preview = Preview {
+            settings: settings.clone(),
+            bounce: BounceSim::new(settings, SCREEN),
+            caption_style: CaptionStyle::new(&settings.info),
+            caption: Vec::new(),
+            placeholder: cover.clone(),
+            cover,
+            info: Vec::new(),
+            palette: palette.unwrap_or(Palette::PLAIN),
+            trail: VecDeque::new(),
+            transition: None,
+            hits: 0,
+            party: None,
+            device: Device {
+                id: None,
+                name: String::from("This computer"),
+                kind: DeviceType::Computer,
+                is_active: true,
+                is_private_session: false,
+                is_restricted: false,
+                volume_percent: None,
+            },
+            started: Instant::now(),
+            last_frame: None,
+        };
+        preview.describe(None);
+        preview
+    }
+
+    /// Shows the artwork in `bytes` as the cover, described by `item`
+    pub fn set_cover(&mut self, bytes: &[u8], item: &PlayingItem) {
+        if let Some((cover, palette)) = renderer::decode_cover(bytes) {
+            let previous = std::mem::replace(&mut self.cover, cover);
+            self.start_transition(previous);
+            self.palette = palette.unwrap_or(Palette::PLAIN);
+        }
+        self.describe(Some(item));
+    }
+
+    /// Describes the cover with the lines of `item`, or sample lines if there is none
+    fn describe(&mut self, item: Option<&PlayingItem>) {
+        self.info = match item {
+            Some(item) if !matches!(item, PlayingItem::Unsupported) => item.info(),
+            _ => vec![
+                String::from("Track title"),
+                String::from("Artists"),
+                String::from("Album"),
+            ],
+        };
+        self.update_caption();
+    }
+
+    /// Follows changes to `settings`, keeping the cover where it is
+    pub fn apply(&mut self, settings: &SaverSettings) {
+        if settings.info != self.settings.info {
+            self.caption_style = CaptionStyle::new(&settings.info);
+        }
+        if !settings.trail {
+            self.trail.clear();
+        }
+        let replay = settings.transition != self.settings.transition;
+        self.settings = settings.clone();
+        // Plays the changed transition onto the cover, so its style and length can be seen
+        if replay {
+            self.start_transition(self.placeholder.clone());
+        }
+        self.bounce.retune(settings);
+        self.update_caption();
+    }
+
+    fn start_transition(&mut self, from: image::Handle) {
+        self.transition = (self.settings.transition.style != TransitionStyle::None)
+            .then(|| CoverTransition::new(&self.settings.transition, from, Instant::now()));
+    }
+
+    /// Moves the cover on to `now`
+    pub fn tick(&mut self, now: Instant) {
+        if let Some(last_frame) = self.last_frame {
+            if let Some(corner) = self.bounce.step(now.duration_since(last_frame)) {
+                self.hits += 1;
+                self.party = Party::start(self.settings.celebration, corner, self.hits, now);
+            }
+        }
+        self.last_frame = Some(now);
+        if self.party.as_ref().is_some_and(|party| party.is_over(now)) {
+            self.party = None;
+        }
+        if self.transition.as_ref().is_some_and(|transition| transition.progress(&self.settings.transition, now).is_none()) {
+            self.transition = None;
+        }
+        let square = renderer::cover_layout(&self.bounce, self.caption_style.as_ref(), self.caption.len()).0.position();
+        if self.settings.trail && self.trail.back().is_none_or(|last| last.distance(square) >= TRAIL_SPACING) {
+            self.trail.push_back(square);
+            if self.trail.len() > TRAIL_LENGTH {
+                self.trail.pop_front();
+            }
+        }
+    }
+
+    fn update_caption(&mut self) {
+        let Some(style) = &self.caption_style else {
+            self.caption.clear();
+            self.bounce.set_caption(0.0);
+            return;
+        };
+        let side = self.bounce.side();
+        self.caption = self.info.iter().map(|line| style.fit(line, side)).collect();
+        self.bounce.set_caption(style.height(self.caption.len()));
+    }
+}
+
+impl<Message> canvas::Program<Message> for Preview {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry> {
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        frame.scale(bounds.width / SCREEN.width);
+        let tint = self.settings.tint;
+        let background = if tint.background() { self.palette.background() } else { Color::BLACK };
+        let highlight = tint.accents().then(|| self.palette.highlight());
+        frame.fill_rectangle(Point::ORIGIN, SCREEN, background);
+
+        let (square, caption_top) = renderer::cover_layout(&self.bounce, self.caption_style.as_ref(), self.caption.len());
+        let (position, side) = (square.position(), square.width);
+        let now = self.last_frame.unwrap_or(self.started);
+        let party_time = self.party.as_ref().map(|party| party.age(now));
+        let square = renderer::pulse(square, self.settings.celebration, party_time);
+
+        renderer::draw_trail(&mut frame, &self.trail, side, highlight.unwrap_or(Color::WHITE));
+        let transition = self.transition.as_ref().and_then(|transition| {
+            transition.progress(&self.settings.transition, now).map(|progress| (transition, progress))
+        });
+        match transition {
+            Some((transition, progress)) => transition.draw(&mut frame, self.settings.transition.style, square, &self.cover, progress),
+            None => frame.draw_image(square, &self.cover),
+        }
+
+        if let Some(style) = &self.caption_style {
+            renderer::draw_caption(&mut frame, style, &self.caption, position.x + side / 2.0, caption_top, highlight.unwrap_or(style.color));
+        }
+
+        let duration_ms = SAMPLE_DURATION.as_millis() as u64;
+        let position_ms = (now.duration_since(self.started).as_millis() as u64) % duration_ms;
+        let color = highlight.unwrap_or(Color::WHITE);
+        renderer::draw_progress(&mut frame, square, position_ms, duration_ms, self.settings.progress_bar, self.settings.progress_times, color);
+
+        if let (Some(party), Some(elapsed)) = (&self.party, party_time) {
+            renderer::draw_party(&mut frame, self.settings.celebration, SCREEN, party, elapsed, self.hits);
+        }
+        if self.settings.predict_corners {
+            renderer::draw_prediction(&mut frame, &self.bounce, self.hits);
+        }
+        if self.settings.show_device {
+            renderer::draw_device(&mut frame, &self.device, SCREEN, highlight.unwrap_or(Color::WHITE));
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
@@ -0,0 +1,1414 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use iced::advanced::graphics::text::Paragraph;
+use iced::advanced::text::{self, Paragraph as _};
+use iced::{
+    alignment::Horizontal, event, keyboard, mouse, widget::{canvas, image, Canvas}, window, Color, Element, Event, Font, Length, Pixels, Point, Rectangle, Renderer, Size, Subscription, Task, Theme, Vector
+};
+use bytes::Bytes;
+use chrono::Local;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::time::sleep;
+
+use crate::artwork::ArtworkCache;
+use crate::bounce::{self, BounceSim, Corner, CornerCounter, SplitMix};
+use crate::display::Display;
+use crate::events::{PlaybackEvent, Poller};
+use crate::history::HistoryLogger;
+use crate::hooks::Hooks;
+use crate::palette::{self, Palette};
+use crate::playback::{clock, Device, DeviceType, Image, PlaybackCommand, PlaybackState};
+use crate::settings::{Celebration, DisplayMode, HistoryWriter, ExtraCovers, ExtraSource, IdleScene, InfoSettings, SaverSettings, Settings, Tint, TransitionSettings, TransitionStyle};
+use crate::transition::CoverTransition;
+use crate::source::{NowPlayingSource, SourceError, SourceKind};
+use crate::spotify::SpotifyError;
+
+/// Shortest time allowed between polls of the player endpoint
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long the idle covers are kept before being fetched again
+const IDLE_REFRESH: Duration = Duration::from_secs(60);
+/// How long each cover is shown while idle
+const IDLE_CYCLE: Duration = Duration::from_secs(8);
+const RECENT_LIMIT: u8 = 10;
+const ALBUM_LIMIT: u8 = 20;
+/// How long a corner celebration lasts
+const CELEBRATION_LENGTH: Duration = Duration::from_millis(2500);
+/// How long the flash and pulse celebrations last, the rest of the celebration only shows the count
+const FLASH_LENGTH: Duration = Duration::from_millis(500);
+const CONFETTI_COUNT: usize = 120;
+/// Pixels per second squared pulling the confetti down
+const CONFETTI_GRAVITY: f32 = 900.0;
+const CONFETTI_COLORS: [Color; 5] = [
+    Color::from_rgb(0.11, 0.73, 0.33),
+    Color::from_rgb(0.95, 0.26, 0.21),
+    Color::from_rgb(1.0, 0.76, 0.03),
+    Color::from_rgb(0.13, 0.59, 0.95),
+    Color::from_rgb(0.91, 0.12, 0.39),
+];
+/// Space between the cover and its text
+const CAPTION_GAP: f32 = 8.0;
+/// Height of the progress bar along the bottom of the cover
+const PROGRESS_HEIGHT: f32 = 4.0;
+/// How long the colors take to blend into the next cover's
+const PALETTE_BLEND: Duration = Duration::from_secs(1);
+/// Copies of the cover left behind in the trail
+pub const TRAIL_LENGTH: usize = 12;
+/// Pixels the cover moves before leaving another copy behind
+pub const TRAIL_SPACING: f32 = 24.0;
+/// How far ahead the corner prediction looks
+const PREDICTION_HORIZON: Duration = Duration::from_secs(600);
+/// How long the ambient gradient takes to go through every hue
+const AMBIENT_PERIOD: Duration = Duration::from_secs(300);
+/// How long a toast confirming a playback control stays on screen
+const TOAST_LENGTH: Duration = Duration::from_secs(2);
+/// How long the toast takes to fade out at the end
+const TOAST_FADE: Duration = Duration::from_millis(500);
+const TOAST_TEXT_SIZE: f32 = 18.0;
+const DEVICE_TEXT_SIZE: f32 = 16.0;
+/// Space between the device line and the edges of the window
+const DEVICE_MARGIN: f32 = 24.0;
+/// How far shift and the arrow keys seek, in milliseconds
+const SEEK_STEP_MS: i64 = 10_000;
+/// How long closing waits for the poller to write out the history
+const SHUTDOWN_WAIT: Duration = Duration::from_secs(1);
+
+/// Artwork shared between the polling thread and the renderer
+struct Snapshot {
+    /// Incremented every time `scene` changes
+    version: u64,
+    scene: Scene,
+    /// Largest scale factor among the windows, written by the renderer so the poller picks sharp enough artwork
+    scale_factor: f32,
+    /// Largest side of the square in logical pixels, written by the renderer for the same reason
+    square_side: f32,
+    /// Position in the playing item, updated on every poll
+    progress: Option<Progress>,
+    /// Outcome of the last playback control, written by the poller
+    toast: Option<Toast>,
+    /// Device the item is playing on, updated on every poll
+    device: Option<Device>,
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Self {
+            version: 0,
+            scene: Scene::default(),
+            scale_factor: 1.0,
+            square_side: 250.0,
+            progress: None,
+            toast: None,
+            device: None,
+        }
+    }
+}
+
+/// Represents a short message confirming a playback control
+#[derive(Clone)]
+struct Toast {
+    text: String,
+    shown_at: Instant,
+}
+
+/// What the poller last found on the account
+#[derive(Default, Clone)]
+enum Scene {
+    /// Waiting for the first response
+    #[default]
+    Loading,
+    /// The currently playing item, and the smaller covers bouncing alongside it
+    Playing(Cover, Vec<Cover>),
+    /// Nothing is playing, with the covers to cycle through if the idle scene shows any
+    Idle(Vec<Cover>),
+}
+
+/// Position in the playing item as of a poll
+#[derive(Clone, Copy)]
+struct Progress {
+    position_ms: u64,
+    duration_ms: u64,
+    playing: bool,
+    polled_at: Instant,
+}
+
+impl Progress {
+    fn from_state(state: &PlaybackState) -> Option<Progress> {
+        let duration_ms = state.drawable_item()?.duration_ms();
+        (duration_ms > 0).then(|| Progress {
+            position_ms: state.progress_ms.unwrap_or_default(),
+            duration_ms,
+            playing: state.is_playing,
+            polled_at: Instant::now(),
+        })
+    }
+
+    /// Position at `now`, moving on from the poll only while playing
+    fn position_ms(&self, now: Instant) -> u64 {
+        let elapsed = if self.playing { now.duration_since(self.polled_at).as_millis() as u64 } else { 0 };
+        (self.position_ms + elapsed).min(self.duration_ms)
+    }
+}
+
+/// Artwork of an item along with what is drawn around it
+#[derive(Clone, Default)]
+struct Cover {
+    /// Decoded artwork, `None` if the item has none
+    handle: Option<image::Handle>,
+    /// Lines describing the item
+    info: Vec<String>,
+    palette: Option<Palette>,
+    /// Address of the artwork, which tells refreshed idle covers apart from those already shown
+    url: Option<String>,
+}
+
+impl Cover {
+    /// Decodes the artwork on the polling thread, so drawing never waits on it and transitions start on a whole image
+    fn decode(bytes: Option<Bytes>, info: Vec<String>) -> Cover {
+        let (handle, palette) = bytes.and_then(|bytes| decode_cover(&bytes)).unzip();
+        Cover {
+            handle,
+            palette: palette.flatten(),
+            info,
+            url: None,
+        }
+    }
+}
+
+/// Decodes artwork into an image along with its colors, `None` if it is not an image
+pub fn decode_cover(bytes: &[u8]) -> Option<(image::Handle, Option<Palette>)> {
+    let decoded = ::image::load_from_memory(bytes).ok()?;
+    let palette = Palette::extract(&decoded);
+    let rgba = decoded.into_rgba8();
+    Some((image::Handle::from_rgba(rgba.width(), rgba.height(), rgba.into_raw()), palette))
+}
+
+/// Where the idle cycle carries on once its covers are refreshed from `current` to `refreshed`
+///
+/// `None` when the artwork is the same, so the covers already decoded are kept and the cycle is not restarted
+fn refreshed_index(current: &[Cover], refreshed: &[Cover], index: usize) -> Option<usize> {
+    let urls = |covers: &[Cover]| covers.iter().map(|cover| cover.url.clone()).collect::<Vec<_>>();
+    (urls(current) != urls(refreshed)).then(|| index.min(refreshed.len().saturating_sub(1)))
+}
+
+/// How the lines describing the cover are drawn
+pub struct CaptionStyle {
+    pub font: Font,
+    pub size: f32,
+    pub color: Color,
+    pub above: bool,
+}
+
+impl CaptionStyle {
+    /// `None` if the text is turned off
+    pub fn new(settings: &InfoSettings) -> Option<CaptionStyle> {
+        settings.show.then(|| CaptionStyle {
+            font: settings.font.as_deref().map_or(Font::DEFAULT, font_named),
+            size: settings.size,
+            color: Color::parse(&settings.color).unwrap_or_else(|| {
+                println!("Could not read color {}, using white", settings.color);
+                Color::WHITE
+            }),
+            above: settings.above,
+        })
+    }
+
+    pub fn line_height(&self) -> f32 {
+        (self.size * 1.3).ceil()
+    }
+
+    /// Height taken by `lines` lines and the gap to the cover
+    pub fn height(&self, lines: usize) -> f32 {
+        if lines == 0 { 0.0 } else { CAPTION_GAP + self.line_height() * lines as f32 }
+    }
+
+    /// Shortens `line` with an ellipsis until it fits in `width`
+    pub fn fit(&self, line: &str, width: f32) -> String {
+        if self.measure(line) <= width {
+            return line.to_string();
+        }
+        let ends: Vec<usize> = line.char_indices().map(|(index, _)| index).skip(1).collect();
+        // Longest prefix that fits, found by bisecting the character boundaries
+        let (mut low, mut high) = (0, ends.len());
+        while low < high {
+            let middle = (low + high).div_ceil(2);
+            if self.measure(&format!("{}…", line[..ends[middle - 1]].trim_end())) <= width {
+                low = middle;
+            } else {
+                high = middle - 1;
+            }
+        }
+        match low {
+            0 => String::from("…"),
+            count => format!("{}…", line[..ends[count - 1]].trim_end()),
+        }
+    }
+
+    fn measure(&self, line: &str) -> f32 {
+        Paragraph::with_text(text::Text {
+            content: line,
+            bounds: Size::INFINITY,
+            size: Pixels(self.size),
+            line_height: text::LineHeight::Absolute(Pixels(self.line_height())),
+            font: self.font,
+            horizontal_alignment: Horizontal::Left,
+            vertical_alignment: iced::alignment::Vertical::Top,
+            shaping: text::Shaping::Advanced,
+            wrapping: text::Wrapping::None,
+        })
+        .min_width()
+    }
+}
+
+/// Font called `name`, whose name is kept for good since fonts are looked up by a static name
+///
+/// Names are kept once each, as the caption is rebuilt whenever the settings change
+fn font_named(name: &str) -> Font {
+    static NAMES: OnceLock<Mutex<HashMap<String, &'static str>>> = OnceLock::new();
+    let mut names = NAMES.get_or_init(Mutex::default).lock().expect("Font lookup panicked");
+    let name = *names.entry(name.to_string()).or_insert_with(|| Box::leak(name.to_string().into_boxed_str()));
+    Font::with_name(name)
+}
+
+/// A corner hit being celebrated
+pub struct Party {
+    corner: Corner,
+    started: Instant,
+    /// Velocity and color of each piece of confetti, empty unless celebrating with confetti
+    confetti: Vec<(Vector, Color)>,
+}
+
+impl Party {
+    /// Starts the configured celebration for the `hits`th hit on `corner`, `None` if corners are not celebrated
+    pub fn start(celebration: Celebration, corner: Corner, hits: u64, now: Instant) -> Option<Party> {
+        if celebration == Celebration::None {
+            return None;
+        }
+        let mut confetti = Vec::new();
+        if celebration == Celebration::Confetti {
+            // Burst into the screen, away from both edges of the corner
+            let direction = match corner {
+                Corner::TopLeft => Vector::new(1.0, 1.0),
+                Corner::TopRight => Vector::new(-1.0, 1.0),
+                Corner::BottomLeft => Vector::new(1.0, -1.0),
+                Corner::BottomRight => Vector::new(-1.0, -1.0),
+            };
+            let mut random = SplitMix(hits);
+            for index in 0..CONFETTI_COUNT {
+                let angle = random.next_f32() * std::f32::consts::FRAC_PI_2;
+                let speed = 300.0 + random.next_f32() * 600.0;
+                confetti.push((
+                    Vector::new(direction.x * angle.cos() * speed, direction.y * angle.sin() * speed),
+                    CONFETTI_COLORS[index % CONFETTI_COLORS.len()],
+                ));
+            }
+        }
+        Some(Party { corner, started: now, confetti })
+    }
+
+    /// How long ago the corner was hit
+    pub fn age(&self, now: Instant) -> Duration {
+        now.duration_since(self.started)
+    }
+
+    pub fn is_over(&self, now: Instant) -> bool {
+        self.age(now) >= CELEBRATION_LENGTH
+    }
+}
+
+/// Where the square bouncing as `bounce` is drawn, and the top of its `lines` lines of text
+///
+/// The square and its caption bounce together, with the square below the caption when that is drawn above
+pub fn cover_layout(bounce: &BounceSim, caption_style: Option<&CaptionStyle>, lines: usize) -> (Rectangle, f32) {
+    let side = bounce.side();
+    let mut position = bounce.position();
+    let mut caption_top = position.y + side + CAPTION_GAP;
+    if let Some(style) = caption_style.filter(|style| style.above) {
+        caption_top = position.y;
+        position.y += style.height(lines);
+    }
+    (Rectangle::new(position, Size::new(side, side)), caption_top)
+}
+
+/// Covers bouncing on one window, and what follows them around
+struct Pane {
+    bounce: BounceSim,
+    /// Seed the smaller covers on this window are placed with
+    seed: Option<u64>,
+    scale_factor: f32,
+    party: Option<Party>,
+    /// Lines describing the shown cover, shortened to its width
+    caption: Vec<String>,
+    /// Recent top left corners of the square, oldest first, empty unless the trail is enabled
+    trail: VecDeque<Point>,
+    /// Smaller covers bouncing alongside the playing one
+    extras: Vec<(BounceSim, Cover)>,
+}
+
+impl Pane {
+    fn new(settings: &SaverSettings, bounds: Size) -> Pane {
+        Pane {
+            bounce: BounceSim::new(settings, bounds),
+            seed: settings.seed,
+            scale_factor: 1.0,
+            party: None,
+            caption: Vec::new(),
+            trail: VecDeque::new(),
+            extras: Vec::new(),
+        }
+    }
+
+    /// Moves every cover by `elapsed`, returning the corner the main one hit
+    fn step(&mut self, elapsed: Duration, now: Instant, trail: bool, caption_style: Option<&CaptionStyle>) -> Option<Corner> {
+        let corner = self.bounce.step(elapsed);
+        for (sim, _) in &mut self.extras {
+            sim.step(elapsed);
+        }
+        let mut sims: Vec<&mut BounceSim> = std::iter::once(&mut self.bounce)
+            .chain(self.extras.iter_mut().map(|(sim, _)| sim))
+            .collect();
+        bounce::collide_all(&mut sims);
+        if self.party.as_ref().is_some_and(|party| party.is_over(now)) {
+            self.party = None;
+        }
+        let square = cover_layout(&self.bounce, caption_style, self.caption.len()).0.position();
+        if trail && self.trail.back().is_none_or(|last| last.distance(square) >= TRAIL_SPACING) {
+            self.trail.push_back(square);
+            if self.trail.len() > TRAIL_LENGTH {
+                self.trail.pop_front();
+            }
+        }
+        corner
+    }
+
+    fn resize(&mut self, size: Size) {
+        self.bounce.resize(size);
+        for (sim, _) in &mut self.extras {
+            sim.resize(size);
+        }
+        self.trail.clear();
+    }
+
+    /// Shows `extras` alongside the main cover, placing `settings` sized covers for any new ones
+    fn show_extras(&mut self, extras: &[Cover], settings: &SaverSettings) {
+        // Covers already bouncing keep moving, only new ones are placed
+        self.extras.truncate(extras.len());
+        for (index, extra) in extras.iter().enumerate() {
+            match self.extras.get_mut(index) {
+                Some((_, shown)) => *shown = extra.clone(),
+                None => {
+                    let settings = SaverSettings {
+                        seed: self.seed.map(|seed| seed.wrapping_add(index as u64 + 1)),
+                        ..settings.clone()
+                    };
+                    self.extras.push((BounceSim::new(&settings, self.bounce.bounds()), extra.clone()));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Message {
+    Tick(Instant),
+    Resized(window::Id, Size),
+    ScaleFactor(window::Id, f32),
+    /// Pauses if playing, plays otherwise
+    TogglePlayback,
+    /// Seeks this many milliseconds from the current position
+    SeekBy(i64),
+    Control(PlaybackCommand),
+    Exit,
+}
+
+struct Screensaver {
+    shared: Arc<Mutex<Snapshot>>,
+    /// Version of the snapshot the handles below were built from
+    version: u64,
+    /// Window on every display covered, `None` for those left blank
+    windows: HashMap<window::Id, Option<Pane>>,
+    /// When the last frame was drawn, `None` before the first one
+    last_frame: Option<Instant>,
+    corners: CornerCounter,
+    celebration: Celebration,
+    predict_corners: bool,
+    placeholder: image::Handle,
+    playing: Cover,
+    /// Whether nothing is playing, and the idle scene is shown
+    idle: bool,
+    idle_scene: IdleScene,
+    /// Covers cycled through while idle, empty unless idle
+    idle_covers: Vec<Cover>,
+    idle_index: usize,
+    last_cycle: Instant,
+    progress: Option<Progress>,
+    progress_bar: bool,
+    progress_times: bool,
+    /// `None` if no text is drawn with the cover
+    caption_style: Option<CaptionStyle>,
+    tint: Tint,
+    /// Palette being blended from, towards the shown cover's palette
+    palette_from: Palette,
+    palette_changed: Instant,
+    show_trail: bool,
+    transition_settings: TransitionSettings,
+    /// `None` unless the cover is changing
+    transition: Option<CoverTransition>,
+    /// Settings the smaller covers are placed with
+    extra_settings: SaverSettings,
+    /// Whether keys control playback instead of closing the screensaver
+    interactive: bool,
+    /// Playback controls waiting to be carried out by the poller
+    commands: UnboundedSender<PlaybackCommand>,
+    /// `None` until a playback control has been carried out
+    toast: Option<Toast>,
+    show_device: bool,
+    /// `None` while nothing plays or the source does not report devices
+    device: Option<Device>,
+    /// Tells the poller to stop, `None` once it has been told
+    shutdown: Option<oneshot::Sender<()>>,
+    /// Hears when the poller has stopped
+    poller_stopped: std::sync::mpsc::Receiver<()>,
+}
+
+impl Screensaver {
+    fn new(source: SourceKind) -> (Screensaver, Task<Message>) {
+        let settings = Settings::load().saver;
+        let (opened, open) = open_windows(settings.displays);
+        // Each window starts its cover somewhere else, even with a seed set
+        let windows = opened
+            .into_iter()
+            .enumerate()
+            .map(|(index, (id, size, shows_cover))| {
+                let seed = settings.seed.map(|seed| seed.wrapping_add((index as u64) << 32));
+                (id, shows_cover.then(|| Pane::new(&SaverSettings { seed, ..settings.clone() }, size)))
+            })
+            .collect();
+        let extra_settings = SaverSettings {
+            size: settings.extras.size,
+            angle: None,
+            ..settings.clone()
+        };
+        let shared = Arc::new(Mutex::new(Snapshot::default()));
+        let (commands, received) = mpsc::unbounded_channel();
+        let (shutdown, shutdown_received) = oneshot::channel();
+        let poller_stopped = spawn_poller(source, shared.clone(), received, shutdown_received);
+        let screensaver = Screensaver {
+        shared: shared.clone(),
+        version: 0,
+        windows,
+        last_frame: None,
+        corners: CornerCounter::load(),
+        celebration: settings.celebration,
+        predict_corners: settings.predict_corners,
+        placeholder: Cover::decode(Some(Bytes::from_static(include_bytes!("../images/placeholder.jpg"))), Vec::new())
+            .handle
+            .expect("Could not decode placeholder"),
+        playing: Cover::default(),
+        idle: false,
+        idle_scene: settings.idle,
+        idle_covers: Vec::new(),
+        idle_index: 0,
+        last_cycle: Instant::now(),
+        progress: None,
+        progress_bar: settings.progress_bar,
+        progress_times: settings.progress_times,
+        caption_style: CaptionStyle::new(&settings.info),
+        tint: settings.tint,
+        palette_from: Palette::PLAIN,
+        palette_changed: Instant::now(),
+        show_trail: settings.trail,
+        transition_settings: settings.transition,
+        transition: None,
+        extra_settings,
+        interactive: settings.interactive,
+        commands,
+        toast: None,
+        show_device: settings.show_device,
+        device: None,
+        shutdown: Some(shutdown),
+        poller_stopped,
+        };
+        screensaver.share_sizes();
+        (screensaver, open)
+    }
+
+    fn title(&self, _id: window::Id) -> String {
+        String::from("Spotify Screensaver")
+    }
+
+    fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::Tick(now) => {
+                self.sync_scene();
+                let elapsed = self.last_frame.map_or(Duration::ZERO, |last_frame| now.duration_since(last_frame));
+                // Nothing bounces over the ambient gradient, so no corner can be hit
+                let still = self.idle && self.idle_scene == IdleScene::Ambient;
+                for pane in self.windows.values_mut().flatten().filter(|_| !still) {
+                    if let Some(corner) = pane.step(elapsed, now, self.show_trail, self.caption_style.as_ref()) {
+                        self.corners.record();
+                        pane.party = Party::start(self.celebration, corner, self.corners.hits, now);
+                    }
+                }
+                self.last_frame = Some(now);
+                if self.transition.as_ref().is_some_and(|transition| transition.progress(&self.transition_settings, now).is_none()) {
+                    self.transition = None;
+                }
+                if !self.idle_covers.is_empty() && now.duration_since(self.last_cycle) >= IDLE_CYCLE {
+                    let (previous, palette) = (self.shown_handle().clone(), self.palette());
+                    self.idle_index = (self.idle_index + 1) % self.idle_covers.len();
+                    self.last_cycle = now;
+                    self.shown_changed(previous, palette);
+                }
+                Task::none()
+            }
+            Message::Resized(id, size) => {
+                if let Some(Some(pane)) = self.windows.get_mut(&id) {
+                    pane.resize(size);
+                }
+                self.update_caption();
+                self.share_sizes();
+                // The window may have moved to a display with a different DPI
+                query_scale_factor(id)
+            }
+            Message::ScaleFactor(id, scale_factor) => {
+                if let Some(Some(pane)) = self.windows.get_mut(&id) {
+                    pane.scale_factor = scale_factor;
+                }
+                self.share_sizes();
+                Task::none()
+            }
+            Message::TogglePlayback => {
+                let playing = self.progress.is_some_and(|progress| progress.playing);
+                self.control(if playing { PlaybackCommand::Pause } else { PlaybackCommand::Play });
+                Task::none()
+            }
+            Message::SeekBy(offset_ms) => {
+                if let (Some(progress), Some(now)) = (self.progress, self.last_frame) {
+                    let position_ms = progress.position_ms(now).saturating_add_signed(offset_ms).min(progress.duration_ms);
+                    self.control(PlaybackCommand::Seek(position_ms));
+                }
+                Task::none()
+            }
+            Message::Control(command) => {
+                self.control(command);
+                Task::none()
+            }
+            Message::Exit => {
+                // Lets the poller write out the history before the process ends
+                if let Some(shutdown) = self.shutdown.take() {
+                    if shutdown.send(()).is_ok() {
+                        let _ = self.poller_stopped.recv_timeout(SHUTDOWN_WAIT);
+                    }
+                }
+                iced::exit()
+            }
+        }
+    }
+
+    /// Hands `command` to the poller, which shows a toast once it is carried out
+    fn control(&self, command: PlaybackCommand) {
+        if self.commands.send(command).is_err() {
+            println!("Could not control playback, the poller has stopped");
+        }
+    }
+
+    fn view(&self, id: window::Id) -> Element<'_, Message> {
+        let pane = self.windows.get(&id).and_then(Option::as_ref);
+        Canvas::new(PaneView { screensaver: self, pane })
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let input: fn(Event, event::Status, window::Id) -> Option<Message> =
+            if self.interactive { playback_input } else { any_input };
+        Subscription::batch([window::frames().map(Message::Tick), event::listen_with(input)])
+    }
+
+    /// Tells the poller the largest cover and scale factor among the windows, so the artwork is sharp on all of them
+    fn share_sizes(&self) {
+        let mut snapshot = self.shared.lock().expect("Poller panicked");
+        let panes = || self.windows.values().flatten();
+        snapshot.square_side = panes().map(|pane| pane.bounce.side()).fold(0.0, f32::max);
+        snapshot.scale_factor = panes().map(|pane| pane.scale_factor).fold(1.0, f32::max);
+    }
+
+    /// Rebuilds image handles if the poller published a new scene
+    fn sync_scene(&mut self) {
+        let snapshot = self.shared.lock().expect("Poller panicked");
+        self.progress = snapshot.progress;
+        self.toast = snapshot.toast.clone();
+        self.device = snapshot.device.clone();
+        if snapshot.version == self.version {
+            return;
+        }
+        self.version = snapshot.version;
+        let (previous, palette) = (self.shown_handle().clone(), self.palette());
+        match &snapshot.scene {
+            Scene::Loading => return,
+            Scene::Playing(cover, extras) => {
+                self.playing = cover.clone();
+                self.idle = false;
+                self.idle_covers.clear();
+                for pane in self.windows.values_mut().flatten() {
+                    pane.show_extras(extras, &self.extra_settings);
+                }
+            }
+            Scene::Idle(covers) => {
+                // A refresh keeps cycling from where it was, otherwise only the first few covers would ever show
+                let index = if self.idle { refreshed_index(&self.idle_covers, covers, self.idle_index) } else { Some(0) };
+                let Some(index) = index else { return };
+                self.playing = Cover::default();
+                for pane in self.windows.values_mut().flatten() {
+                    pane.extras.clear();
+                }
+                if !self.idle {
+                    self.last_cycle = Instant::now();
+                }
+                self.idle = true;
+                self.idle_covers = covers.clone();
+                self.idle_index = index;
+            }
+        }
+        drop(snapshot);
+        self.shown_changed(previous, palette);
+    }
+
+    /// The cover on screen, one of the idle covers while idle
+    fn shown(&self) -> &Cover {
+        self.idle_covers.get(self.idle_index).unwrap_or(&self.playing)
+    }
+
+    fn shown_handle(&self) -> &image::Handle {
+        self.shown().handle.as_ref().unwrap_or(&self.placeholder)
+    }
+
+    /// Starts the transition away from `previous`, blends from `palette` towards the new cover's colors and fits its text
+    fn shown_changed(&mut self, previous: image::Handle, palette: Palette) {
+        if previous.id() != self.shown_handle().id() && self.transition_settings.style != TransitionStyle::None {
+            self.transition = Some(CoverTransition::new(&self.transition_settings, previous, Instant::now()));
+        }
+        // Read before the cover changed, the blend would otherwise jump to partway towards the new colors
+        self.palette_from = palette;
+        self.palette_changed = Instant::now();
+        self.update_caption();
+    }
+
+    /// Colors at this point of the blend between the previous and the shown cover's palettes
+    fn palette(&self) -> Palette {
+        let target = self.shown().palette.unwrap_or(Palette::PLAIN);
+        let amount = self.palette_changed.elapsed().as_secs_f32() / PALETTE_BLEND.as_secs_f32();
+        self.palette_from.lerp(&target, amount.min(1.0))
+    }
+
+    /// Shortens the lines of the shown cover to its width, and lets them bounce along with it
+    fn update_caption(&mut self) {
+        let Some(style) = &self.caption_style else { return };
+        let info = self.shown().info.clone();
+        for pane in self.windows.values_mut().flatten() {
+            let side = pane.bounce.side();
+            pane.caption = info.iter().map(|line| style.fit(line, side)).collect();
+            pane.bounce.set_caption(style.height(pane.caption.len()));
+        }
+    }
+}
+
+/// Draws one window of the screensaver
+struct PaneView<'a> {
+    screensaver: &'a Screensaver,
+    /// `None` if the window is left blank
+    pane: Option<&'a Pane>,
+}
+
+impl canvas::Program<Message> for PaneView<'_> {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry> {
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        let Some(pane) = self.pane else {
+            frame.fill_rectangle(Point::ORIGIN, bounds.size(), Color::BLACK);
+            return vec![frame.into_geometry()];
+        };
+        self.screensaver.draw_pane(&mut frame, pane, bounds);
+        vec![frame.into_geometry()]
+    }
+}
+
+impl Screensaver {
+    fn draw_pane(&self, frame: &mut canvas::Frame, pane: &Pane, bounds: Rectangle) {
+        if self.idle && self.idle_scene == IdleScene::Ambient {
+            draw_ambient(frame, bounds.size());
+            self.draw_toast(frame, bounds.size());
+            return;
+        }
+        let palette = self.palette();
+        let background = if self.tint.background() { palette.background() } else { Color::BLACK };
+        let highlight = self.tint.accents().then(|| palette.highlight());
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), background);
+
+        let (mut square, caption_top) = cover_layout(&pane.bounce, self.caption_style.as_ref(), pane.caption.len());
+        let (position, side) = (square.position(), square.width);
+        let party_time = pane.party.as_ref().zip(self.last_frame).map(|(party, now)| party.age(now));
+        square = pulse(square, self.celebration, party_time);
+
+        for (sim, extra) in &pane.extras {
+            let extra_side = sim.side();
+            let handle = extra.handle.as_ref().unwrap_or(&self.placeholder);
+            frame.draw_image(Rectangle::new(sim.position(), Size::new(extra_side, extra_side)), handle);
+        }
+
+        draw_trail(frame, &pane.trail, side, highlight.unwrap_or(Color::WHITE));
+
+        let transition = self.transition.as_ref().zip(self.last_frame).and_then(|(transition, now)| {
+            transition.progress(&self.transition_settings, now).map(|progress| (transition, progress))
+        });
+        match transition {
+            _ if self.idle && matches!(self.idle_scene, IdleScene::Clock | IdleScene::Date) => {
+                let color = highlight.or(self.caption_style.as_ref().map(|style| style.color)).unwrap_or(Color::WHITE);
+                self.draw_time(frame, square, color);
+            }
+            Some((transition, progress)) => transition.draw(frame, self.transition_settings.style, square, self.shown_handle(), progress),
+            None => frame.draw_image(square, self.shown_handle()),
+        }
+
+        if let Some(style) = &self.caption_style {
+            draw_caption(frame, style, &pane.caption, position.x + side / 2.0, caption_top, highlight.unwrap_or(style.color));
+        }
+
+        if let (Some(progress), Some(now), true) = (self.progress, self.last_frame, !self.idle) {
+            let color = highlight.unwrap_or(Color::WHITE);
+            draw_progress(frame, square, progress.position_ms(now), progress.duration_ms, self.progress_bar, self.progress_times, color);
+        }
+
+        if let (Some(party), Some(elapsed)) = (&pane.party, party_time) {
+            draw_party(frame, self.celebration, pane.bounce.bounds(), party, elapsed, self.corners.hits);
+        }
+        if self.predict_corners {
+            draw_prediction(frame, &pane.bounce, self.corners.hits);
+        }
+        if let (Some(device), true, false) = (&self.device, self.show_device, self.idle) {
+            draw_device(frame, device, bounds.size(), highlight.unwrap_or(Color::WHITE));
+        }
+        self.draw_toast(frame, bounds.size());
+    }
+
+    /// Draws the last toast centered near the bottom of the window, fading out before it goes
+    fn draw_toast(&self, frame: &mut canvas::Frame, size: Size) {
+        let (Some(toast), Some(now)) = (&self.toast, self.last_frame) else { return };
+        let Some(remaining) = TOAST_LENGTH.checked_sub(now.duration_since(toast.shown_at)) else { return };
+        let alpha = (remaining.as_secs_f32() / TOAST_FADE.as_secs_f32()).min(1.0);
+        // Same estimate of the text's width as the clock uses
+        let width = 0.6 * TOAST_TEXT_SIZE * toast.text.chars().count() as f32 + 32.0;
+        let height = TOAST_TEXT_SIZE + 20.0;
+        let top = size.height - height - 48.0;
+        frame.fill_rectangle(
+            Point::new((size.width - width) / 2.0, top),
+            Size::new(width, height),
+            Color { a: 0.7 * alpha, ..Color::BLACK },
+        );
+        frame.fill_text(canvas::Text {
+            content: toast.text.clone(),
+            position: Point::new(size.width / 2.0, top + 10.0),
+            color: Color { a: alpha, ..Color::WHITE },
+            size: Pixels(TOAST_TEXT_SIZE),
+            horizontal_alignment: Horizontal::Center,
+            shaping: text::Shaping::Advanced,
+            ..canvas::Text::default()
+        });
+    }
+
+    /// Draws the time, or the day and date, across the square in place of a cover
+    fn draw_time(&self, frame: &mut canvas::Frame, square: Rectangle, color: Color) {
+        let now = Local::now();
+        let lines = match self.idle_scene {
+            IdleScene::Date => vec![now.format("%A").to_string(), now.format("%-d %B").to_string()],
+            _ => vec![now.format("%H:%M").to_string()],
+        };
+        // Characters are roughly 0.6 of the text size wide, which is close enough to fit the longest line
+        let longest = lines.iter().map(|line| line.chars().count()).max().unwrap_or(1) as f32;
+        let size = (square.width * 0.9 / (0.6 * longest)).min(square.height / (1.3 * lines.len() as f32));
+        let top = square.center_y() - size * 1.3 * lines.len() as f32 / 2.0;
+        for (index, line) in lines.into_iter().enumerate() {
+            frame.fill_text(canvas::Text {
+                content: line,
+                position: Point::new(square.center_x(), top + size * 1.3 * index as f32),
+                color,
+                size: Pixels(size),
+                line_height: text::LineHeight::Relative(1.3),
+                font: self.caption_style.as_ref().map_or(Font::DEFAULT, |style| style.font),
+                horizontal_alignment: Horizontal::Center,
+                shaping: text::Shaping::Advanced,
+                ..canvas::Text::default()
+            });
+        }
+    }
+}
+
+/// `square` grown by the pulse celebrating a corner hit `party_time` ago
+pub fn pulse(square: Rectangle, celebration: Celebration, party_time: Option<Duration>) -> Rectangle {
+    let Some(elapsed) = party_time.filter(|elapsed| celebration == Celebration::Pulse && *elapsed < FLASH_LENGTH) else {
+        return square;
+    };
+    let grown = square.width * (1.0 + 0.15 * (std::f32::consts::PI * elapsed.as_secs_f32() / FLASH_LENGTH.as_secs_f32()).sin());
+    Rectangle::new(
+        Point::new(square.center_x() - grown / 2.0, square.center_y() - grown / 2.0),
+        Size::new(grown, grown),
+    )
+}
+
+/// Draws squares of `side` where the cover was, the oldest faintest
+pub fn draw_trail(frame: &mut canvas::Frame, trail: &VecDeque<Point>, side: f32, color: Color) {
+    for (index, corner) in trail.iter().enumerate() {
+        let alpha = 0.25 * (index + 1) as f32 / (trail.len() + 1) as f32;
+        frame.fill_rectangle(*corner, Size::new(side, side), Color { a: alpha, ..color });
+    }
+}
+
+/// Draws `lines` centered on `center_x`, the first one at `top`
+pub fn draw_caption(frame: &mut canvas::Frame, style: &CaptionStyle, lines: &[String], center_x: f32, top: f32, color: Color) {
+    for (index, line) in lines.iter().enumerate() {
+        frame.fill_text(canvas::Text {
+            content: line.clone(),
+            position: Point::new(center_x, top + style.line_height() * index as f32),
+            color,
+            size: Pixels(style.size),
+            line_height: text::LineHeight::Absolute(Pixels(style.line_height())),
+            font: style.font,
+            horizontal_alignment: Horizontal::Center,
+            shaping: text::Shaping::Advanced,
+            ..canvas::Text::default()
+        });
+    }
+}
+
+/// Draws a thin bar along the bottom of the cover if `bar` is set, and the elapsed and remaining time above it if `times` is
+pub fn draw_progress(frame: &mut canvas::Frame, square: Rectangle, position_ms: u64, duration_ms: u64, bar: bool, times: bool, color: Color) {
+    let top = square.y + square.height - PROGRESS_HEIGHT;
+    if times {
+        frame.fill_rectangle(Point::new(square.x, top - 20.0), Size::new(square.width, 20.0), Color { a: 0.5, ..Color::BLACK });
+        let text = |content: String, x: f32, alignment: Horizontal| canvas::Text {
+            content,
+            position: Point::new(x, top - 18.0),
+            color: Color::WHITE,
+            size: Pixels(12.0),
+            horizontal_alignment: alignment,
+            ..canvas::Text::default()
+        };
+        frame.fill_text(text(clock(position_ms), square.x + 6.0, Horizontal::Left));
+        frame.fill_text(text(format!("-{}", clock(duration_ms.saturating_sub(position_ms))), square.x + square.width - 6.0, Horizontal::Right));
+    }
+    if bar {
+        let played = square.width * position_ms as f32 / duration_ms.max(1) as f32;
+        frame.fill_rectangle(Point::new(square.x, top), Size::new(square.width, PROGRESS_HEIGHT), Color { a: 0.3, ..Color::WHITE });
+        frame.fill_rectangle(Point::new(square.x, top), Size::new(played, PROGRESS_HEIGHT), color);
+    }
+}
+
+/// Draws the flash, confetti and count of the `hits`th corner hit, `elapsed` after it
+pub fn draw_party(frame: &mut canvas::Frame, celebration: Celebration, bounds: Size, party: &Party, elapsed: Duration, hits: u64) {
+    let progress = elapsed.as_secs_f32() / CELEBRATION_LENGTH.as_secs_f32();
+    if celebration == Celebration::Flash && elapsed < FLASH_LENGTH {
+        let alpha = 0.6 * (1.0 - elapsed.as_secs_f32() / FLASH_LENGTH.as_secs_f32());
+        frame.fill_rectangle(Point::ORIGIN, bounds, Color { a: alpha, ..Color::WHITE });
+    }
+
+    let origin = party.corner.point(bounds);
+    let seconds = elapsed.as_secs_f32();
+    for (velocity, color) in &party.confetti {
+        let position = Point::new(
+            origin.x + velocity.x * seconds,
+            origin.y + velocity.y * seconds + CONFETTI_GRAVITY * seconds * seconds / 2.0,
+        );
+        frame.fill_rectangle(position, Size::new(8.0, 5.0), Color { a: 1.0 - progress, ..*color });
+    }
+
+    frame.fill_text(canvas::Text {
+        content: format!("Corner hit #{}", hits),
+        position: Point::new(bounds.width / 2.0, 24.0),
+        color: Color { a: 1.0 - progress, ..Color::WHITE },
+        size: Pixels(28.0),
+        horizontal_alignment: Horizontal::Center,
+        ..canvas::Text::default()
+    });
+}
+
+/// Marks the corner `bounce` will hit next, and when
+pub fn draw_prediction(frame: &mut canvas::Frame, bounce: &BounceSim, hits: u64) {
+    let bounds = bounce.bounds();
+    let content = match bounce.next_corner(PREDICTION_HORIZON) {
+        Some((corner, after)) => {
+            frame.fill(&canvas::Path::circle(corner.point(bounds), 16.0), Color::from_rgb(0.95, 0.26, 0.21));
+            format!("Next corner: {} in {:.1}s", corner.label(), after.as_secs_f32())
+        }
+        None => format!("No corner hit in the next {} minutes", PREDICTION_HORIZON.as_secs() / 60),
+    };
+    frame.fill_text(canvas::Text {
+        content: format!("{content} ({} hits so far)", hits),
+        position: Point::new(24.0, 24.0),
+        color: Color::from_rgb(0.6, 0.6, 0.6),
+        size: Pixels(14.0),
+        ..canvas::Text::default()
+    });
+}
+
+/// Fills `size` with a gradient that slowly turns through every hue
+fn draw_ambient(frame: &mut canvas::Frame, size: Size) {
+    // Wall clock time keeps every window on the same colors
+    let period = AMBIENT_PERIOD.as_millis() as i64;
+    let cycle = Local::now().timestamp_millis().rem_euclid(period) as f32 / period as f32;
+    let angle = cycle * 2.0 * std::f32::consts::PI;
+    let center = Point::new(size.width / 2.0, size.height / 2.0);
+    let reach = Vector::new(angle.cos(), angle.sin()) * (size.width.max(size.height) / 2.0);
+    let gradient = canvas::gradient::Linear::new(center - reach, center + reach)
+        .add_stop(0.0, palette::hsv(cycle, 0.6, 0.35))
+        .add_stop(1.0, palette::hsv(cycle + 0.15, 0.7, 0.2));
+    frame.fill_rectangle(Point::ORIGIN, size, gradient);
+}
+
+/// Draws the device's icon and "Playing on <name>" in the bottom left corner of the window
+pub fn draw_device(frame: &mut canvas::Frame, device: &Device, size: Size, color: Color) {
+    let color = Color { a: 0.8, ..color };
+    let top = size.height - DEVICE_MARGIN - DEVICE_TEXT_SIZE;
+    let icon = device_icon(device.kind, Point::new(DEVICE_MARGIN, top), DEVICE_TEXT_SIZE);
+    frame.stroke(&icon, canvas::Stroke::default().with_color(color).with_width(1.5));
+    frame.fill_text(canvas::Text {
+        content: format!("Playing on {}", device.name),
+        position: Point::new(DEVICE_MARGIN + DEVICE_TEXT_SIZE * 1.5, top),
+        color,
+        size: Pixels(DEVICE_TEXT_SIZE),
+        line_height: text::LineHeight::Absolute(Pixels(DEVICE_TEXT_SIZE)),
+        shaping: text::Shaping::Advanced,
+        ..canvas::Text::default()
+    });
+}
+
+/// Outline of a `kind` of device, fitting a square of `side` at `origin`
+fn device_icon(kind: DeviceType, origin: Point, side: f32) -> canvas::Path {
+    // Shapes are laid out on a unit square
+    let at = |x: f32, y: f32| Point::new(origin.x + x * side, origin.y + y * side);
+    let rectangle = |builder: &mut canvas::path::Builder, x: f32, y: f32, width: f32, height: f32| {
+        builder.rectangle(at(x, y), Size::new(width * side, height * side));
+    };
+    let circle = |builder: &mut canvas::path::Builder, x: f32, y: f32, radius: f32| {
+        builder.circle(at(x, y), radius * side);
+    };
+    canvas::Path::new(|builder| match kind {
+        DeviceType::Computer => {
+            rectangle(builder, 0.05, 0.1, 0.9, 0.6);
+            builder.move_to(at(0.5, 0.7));
+            builder.line_to(at(0.5, 0.9));
+            builder.move_to(at(0.3, 0.9));
+            builder.line_to(at(0.7, 0.9));
+        }
+        DeviceType::Smartphone => {
+            rectangle(builder, 0.28, 0.02, 0.44, 0.96);
+            builder.move_to(at(0.42, 0.86));
+            builder.line_to(at(0.58, 0.86));
+        }
+        DeviceType::Tablet => {
+            rectangle(builder, 0.12, 0.08, 0.76, 0.84);
+            builder.move_to(at(0.42, 0.82));
+            builder.line_to(at(0.58, 0.82));
+        }
+        DeviceType::Smartwatch => {
+            rectangle(builder, 0.28, 0.25, 0.44, 0.5);
+            for x in [0.38, 0.62] {
+                builder.move_to(at(x, 0.02));
+                builder.line_to(at(x, 0.25));
+                builder.move_to(at(x, 0.75));
+                builder.line_to(at(x, 0.98));
+            }
+        }
+        DeviceType::Speaker | DeviceType::Avr | DeviceType::AudioDongle | DeviceType::CastAudio => {
+            rectangle(builder, 0.2, 0.02, 0.6, 0.96);
+            circle(builder, 0.5, 0.25, 0.08);
+            circle(builder, 0.5, 0.65, 0.2);
+        }
+        DeviceType::Tv | DeviceType::Stb | DeviceType::CastVideo | DeviceType::GameConsole => {
+            rectangle(builder, 0.02, 0.12, 0.96, 0.62);
+            builder.move_to(at(0.5, 0.74));
+            builder.line_to(at(0.5, 0.9));
+            builder.move_to(at(0.3, 0.9));
+            builder.line_to(at(0.7, 0.9));
+        }
+        DeviceType::Automobile => {
+            rectangle(builder, 0.02, 0.42, 0.96, 0.32);
+            builder.move_to(at(0.18, 0.42));
+            builder.line_to(at(0.3, 0.16));
+            builder.line_to(at(0.7, 0.16));
+            builder.line_to(at(0.82, 0.42));
+            circle(builder, 0.27, 0.8, 0.12);
+            circle(builder, 0.73, 0.8, 0.12);
+        }
+        // A note, for anything Spotify does not name
+        DeviceType::Unknown => {
+            circle(builder, 0.35, 0.78, 0.15);
+            builder.move_to(at(0.5, 0.78));
+            builder.line_to(at(0.5, 0.08));
+            builder.line_to(at(0.82, 0.2));
+        }
+    })
+}
+
+/// Opens the windows `mode` asks for, returning each one's id, its size and whether it shows a cover
+fn open_windows(mode: DisplayMode) -> (Vec<(window::Id, Size, bool)>, Task<Message>) {
+    let displays = Display::all();
+    let (targets, fullscreen) = match mode {
+        // A fullscreen window would only cover one display
+        DisplayMode::Span => (Display::span(&displays).into_iter().collect(), false),
+        DisplayMode::Each | DisplayMode::Primary => (displays, true),
+    };
+    if targets.is_empty() {
+        // Replaced by the real size once the window goes fullscreen
+        let (id, open) = window::open(window::Settings::default());
+        return (vec![(id, Size::new(1280.0, 720.0), true)], open.then(|id| fill_display(id, true)));
+    }
+
+    let mut windows = Vec::new();
+    let mut tasks = Vec::new();
+    for (index, display) in targets.iter().enumerate() {
+        let (id, open) = window::open(window::Settings {
+            size: display.bounds.size(),
+            position: window::Position::Specific(display.bounds.position()),
+            decorations: false,
+            level: window::Level::AlwaysOnTop,
+            ..window::Settings::default()
+        });
+        // The primary display is listed first
+        windows.push((id, display.bounds.size(), mode != DisplayMode::Primary || index == 0));
+        tasks.push(open.then(move |id| fill_display(id, fullscreen)));
+    }
+    (windows, Task::batch(tasks))
+}
+
+/// Makes a newly opened window fullscreen on its display if asked to, and reads its scale factor
+fn fill_display(id: window::Id, fullscreen: bool) -> Task<Message> {
+    if fullscreen {
+        window::change_mode(id, window::Mode::Fullscreen).chain(query_scale_factor(id))
+    } else {
+        query_scale_factor(id)
+    }
+}
+
+fn query_scale_factor(id: window::Id) -> Task<Message> {
+    window::get_scale_factor(id).map(move |scale_factor| Message::ScaleFactor(id, scale_factor))
+}
+
+/// Closes the screensaver on any key or click
+fn any_input(event: Event, _status: event::Status, id: window::Id) -> Option<Message> {
+    match event {
+        Event::Keyboard(keyboard::Event::KeyPressed { .. })
+        | Event::Mouse(mouse::Event::ButtonPressed(_)) => Some(Message::Exit),
+        Event::Window(window::Event::Resized(size)) => Some(Message::Resized(id, size)),
+        _ => None,
+    }
+}
+
+/// Controls playback with the space bar, arrows and media keys, any other key or click closes the screensaver
+fn playback_input(event: Event, status: event::Status, id: window::Id) -> Option<Message> {
+    use keyboard::key::Named;
+
+    let Event::Keyboard(keyboard::Event::KeyPressed { key: keyboard::Key::Named(named), modifiers, .. }) = &event else {
+        return any_input(event, status, id);
+    };
+    match named {
+        Named::Space | Named::MediaPlayPause => Some(Message::TogglePlayback),
+        Named::MediaPlay => Some(Message::Control(PlaybackCommand::Play)),
+        Named::MediaPause => Some(Message::Control(PlaybackCommand::Pause)),
+        Named::ArrowRight if modifiers.shift() => Some(Message::SeekBy(SEEK_STEP_MS)),
+        Named::ArrowLeft if modifiers.shift() => Some(Message::SeekBy(-SEEK_STEP_MS)),
+        Named::ArrowRight | Named::MediaTrackNext => Some(Message::Control(PlaybackCommand::Next)),
+        Named::ArrowLeft | Named::MediaTrackPrevious => Some(Message::Control(PlaybackCommand::Previous)),
+        // Shift alone is held down before seeking
+        Named::Shift => None,
+        _ => any_input(event, status, id),
+    }
+}
+
+/// Runs the screensaver on the displays chosen in the settings until any input is received
+pub fn run(source: SourceKind) -> iced::Result {
+    iced::daemon(Screensaver::title, Screensaver::update, Screensaver::view)
+        .subscription(Screensaver::subscription)
+        .run_with(move || Screensaver::new(source))
+}
+
+/// Polls `source` on a background thread, publishing artwork to `shared` and carrying out `commands`
+///
+/// The poller stops once `shutdown` is sent, and the returned receiver hears when it has finished
+fn spawn_poller(
+    source: SourceKind,
+    shared: Arc<Mutex<Snapshot>>,
+    commands: UnboundedReceiver<PlaybackCommand>,
+    shutdown: oneshot::Receiver<()>,
+) -> std::sync::mpsc::Receiver<()> {
+    let (finished, stopped) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().expect("Could not start runtime");
+        runtime.block_on(async move {
+            let settings = Settings::load();
+            match source.connect(Some(settings.saver.idle)).await {
+                Ok(source) => poll(source, settings, shared, commands, shutdown).await,
+                Err(e) => println!("Could not connect to playback source: {e}"),
+            }
+        });
+        let _ = finished.send(());
+    });
+    stopped
+}
+
+async fn poll(
+    source: impl NowPlayingSource,
+    settings: Settings,
+    shared: Arc<Mutex<Snapshot>>,
+    mut commands: UnboundedReceiver<PlaybackCommand>,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    let mut cache = ArtworkCache::open_default().expect("Could not open artwork cache");
+    let mut poller = Poller::new(source, Duration::from_millis(settings.saver.poll_interval_ms).max(MIN_POLL_INTERVAL));
+    let extras = settings.saver.extras.clone();
+    // Extra covers are fetched relative to the main one, whose size the renderer keeps up to date
+    let extra_scale = extras.size / settings.saver.size.max(0.01);
+    let mut history = HistoryLogger::from_settings(&settings.history, HistoryWriter::Saver);
+    let hooks = settings.hooks.in_saver.then(|| Hooks::new(settings.hooks));
+    let mut idle = false;
+    let idle_scene = settings.saver.idle;
+    // When the idle covers were last fetched, `None` if they need fetching
+    let mut idle_fetched: Option<Instant> = None;
+    loop {
+        // Dropping the step mid-poll is fine, the history is only updated between awaits
+        let step = async {
+            match poller.poll().await {
+                Ok(events) => {
+                    {
+                        let mut snapshot = shared.lock().expect("Renderer panicked");
+                        snapshot.progress = poller.state().and_then(Progress::from_state);
+                        snapshot.device = poller.state().and_then(|state| state.device.clone());
+                    }
+                    for event in events {
+                        if let Some(hooks) = &hooks {
+                            hooks.on_event(&event);
+                        }
+                        if let Some(history) = &mut history {
+                            history.on_event(&event);
+                        }
+                        let Some(item) = event.item() else {
+                            match event {
+                                PlaybackEvent::Stopped => {
+                                    idle = true;
+                                    idle_fetched = None;
+                                }
+                                PlaybackEvent::AuthLost => println!("Saved login is no longer valid, sign in again through the installer"),
+                                _ => {}
+                            }
+                            continue;
+                        };
+                        idle = false;
+                        // Local files have no artwork, and are drawn with the placeholder
+                        let images = item.images();
+                        let bytes = cache.fetch_best(images, target_side(&shared)).await;
+                        // Each new item moves the queue along, so the extras are fetched again
+                        let extra_side = (target_side(&shared) as f32 * extra_scale).ceil() as u32;
+                        let extra_covers = extra_covers(poller.source_mut(), &mut cache, &extras, images, extra_side).await;
+                        publish(&shared, Scene::Playing(Cover::decode(bytes, item.info()), extra_covers));
+                    }
+                }
+                Err(e) => println!("Could not fetch playback: {e}"),
+            }
+
+            // Scenes drawn without covers only need publishing once
+            let refresh = idle_fetched.is_none_or(|fetched| idle_scene.shows_covers() && fetched.elapsed() >= IDLE_REFRESH);
+            if idle && refresh {
+                match idle_covers(poller.source_mut(), &mut cache, &shared, idle_scene).await {
+                    Ok(covers) => publish(&shared, Scene::Idle(covers)),
+                    Err(e) => println!("Could not fetch idle covers: {e}"),
+                }
+                idle_fetched = Some(Instant::now());
+            }
+            // A playback control cuts the wait short, so its effect is read straight away
+            tokio::select! {
+                _ = sleep(poller.interval()) => {}
+                Some(command) = commands.recv() => {
+                    let result = poller.source_mut().control(command.clone()).await;
+                    shared.lock().expect("Renderer panicked").toast = Some(Toast {
+                        text: toast_text(&command, result),
+                        shown_at: Instant::now(),
+                    });
+                }
+            }
+        };
+        tokio::select! {
+            _ = step => {}
+            _ = &mut shutdown => break,
+        }
+    }
+    // The item still playing would otherwise be lost
+    if let Some(history) = history {
+        history.close();
+    }
+}
+
+/// Describes the outcome of a playback control
+fn toast_text(command: &PlaybackCommand, result: Result<(), SourceError>) -> String {
+    match result {
+        Ok(()) => command.done(),
+        // Worth spelling out, nothing else on screen explains why the keys do nothing
+        Err(SourceError::Spotify(SpotifyError::PremiumRequired)) => String::from("Spotify Premium is needed to control playback"),
+        Err(e) => {
+            println!("Could not {}: {e}", command.action());
+            format!("Could not {}", command.action())
+        }
+    }
+}
+
+/// Downloads the covers `scene` cycles through, none for scenes drawn without covers
+async fn idle_covers(
+    source: &mut impl NowPlayingSource,
+    cache: &mut ArtworkCache,
+    shared: &Arc<Mutex<Snapshot>>,
+    scene: IdleScene,
+) -> Result<Vec<Cover>, SourceError> {
+    match scene {
+        IdleScene::Recent => recent_covers(source, cache, shared).await,
+        IdleScene::Albums => album_covers(source, cache, shared).await,
+        IdleScene::Clock | IdleScene::Date | IdleScene::Ambient => Ok(Vec::new()),
+    }
+}
+
+/// Downloads the artwork of the user's recently played tracks, skipping repeated albums
+async fn recent_covers(
+    source: &mut impl NowPlayingSource,
+    cache: &mut ArtworkCache,
+    shared: &Arc<Mutex<Snapshot>>,
+) -> Result<Vec<Cover>, SourceError> {
+    let history = source.recently_played(RECENT_LIMIT).await?;
+    let mut seen: Vec<&str> = Vec::new();
+    let mut covers = Vec::new();
+    for entry in &history {
+        let images = &entry.track.album.images;
+        let Some(url) = images.first().map(|image| image.url.as_str()) else { continue };
+        if seen.contains(&url) {
+            continue;
+        }
+        seen.push(url);
+        if let Some(bytes) = cache.fetch_best(images, target_side(shared)).await {
+            let track = &entry.track;
+            let info = vec![format!("Last played: {}", track.name), track.artist_names(), track.album.name.clone()];
+            covers.push(Cover { url: Some(url.to_string()), ..Cover::decode(Some(bytes), info) });
+        }
+    }
+    Ok(covers)
+}
+
+/// Downloads the artwork of the user's saved albums
+async fn album_covers(
+    source: &mut impl NowPlayingSource,
+    cache: &mut ArtworkCache,
+    shared: &Arc<Mutex<Snapshot>>,
+) -> Result<Vec<Cover>, SourceError> {
+    let albums = source.saved_albums(ALBUM_LIMIT).await?;
+    let mut covers = Vec::new();
+    for album in &albums {
+        if let Some(bytes) = cache.fetch_best(&album.images, target_side(shared)).await {
+            let cover = Cover::decode(Some(bytes), vec![album.name.clone(), album.artist_names()]);
+            covers.push(Cover { url: album.images.first().map(|image| image.url.clone()), ..cover });
+        }
+    }
+    Ok(covers)
+}
+
+/// Downloads the artwork of the queue and recently played tracks, skipping `playing` and repeated albums
+///
+/// Errors are logged and leave out the covers they concern
+async fn extra_covers(
+    source: &mut impl NowPlayingSource,
+    cache: &mut ArtworkCache,
+    settings: &ExtraCovers,
+    playing: &[Image],
+    side: u32,
+) -> Vec<Cover> {
+    let mut candidates: Vec<Vec<Image>> = Vec::new();
+    if matches!(settings.from, ExtraSource::Queue | ExtraSource::Both) {
+        match source.queue(settings.count).await {
+            Ok(queue) => candidates.extend(queue.iter().map(|item| item.images().to_vec())),
+            Err(e) => println!("Could not fetch the queue: {e}"),
+        }
+    }
+    if matches!(settings.from, ExtraSource::Recent | ExtraSource::Both) {
+        match source.recently_played(RECENT_LIMIT).await {
+            Ok(recent) => candidates.extend(recent.into_iter().map(|entry| entry.track.album.images)),
+            Err(e) => println!("Could not fetch recently played: {e}"),
+        }
+    }
+
+    let mut seen: Vec<String> = playing.first().map(|image| image.url.clone()).into_iter().collect();
+    let mut covers = Vec::new();
+    for images in candidates {
+        if covers.len() >= settings.count {
+            break;
+        }
+        let Some(url) = images.first().map(|image| image.url.clone()) else { continue };
+        if seen.contains(&url) {
+            continue;
+        }
+        seen.push(url);
+        if let Some(bytes) = cache.fetch_best(&images, side).await {
+            covers.push(Cover::decode(Some(bytes), Vec::new()));
+        }
+    }
+    covers
+}
+
+/// Side of the drawn square in physical pixels
+fn target_side(shared: &Arc<Mutex<Snapshot>>) -> u32 {
+    let snapshot = shared.lock().expect("Renderer panicked");
+    (snapshot.square_side * snapshot.scale_factor).ceil() as u32
+}
+
+fn publish(shared: &Arc<Mutex<Snapshot>>, scene: Scene) {
+    let mut snapshot = shared.lock().expect("Renderer panicked");
+    snapshot.version += 1;
+    snapshot.scene = scene;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Idle covers with artwork at each of `urls`
+    fn covers(urls: &[&str]) -> Vec<Cover> {
+        urls.iter().map(|url| Cover { url: Some(url.to_string()), ..Cover::default() }).collect()
+    }
+
+    #[test]
+    fn refreshing_the_same_covers_keeps_the_cycle() {
+        let shown = covers(&["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"]);
+        assert_eq!(refreshed_index(&shown, &shown.clone(), 8), None);
+    }
+
+    #[test]
+    fn refreshing_saved_albums_reaches_past_the_first_few() {
+        // Twenty albums take longer to cycle through than the minute between refreshes
+        let albums: Vec<String> = (0..ALBUM_LIMIT).map(|index| format!("album {index}")).collect();
+        let albums = covers(&albums.iter().map(String::as_str).collect::<Vec<_>>());
+        assert_eq!(refreshed_index(&albums, &albums.clone(), 15), None);
+    }
+
+    #[test]
+    fn refreshing_other_covers_keeps_the_index_within_them() {
+        let shown = covers(&["a", "b", "c", "d", "e"]);
+        assert_eq!(refreshed_index(&shown, &covers(&["z", "a", "b", "c", "d"]), 3), Some(3));
+        assert_eq!(refreshed_index(&shown, &covers(&["z", "a"]), 3), Some(1));
+        assert_eq!(refreshed_index(&shown, &[], 3), Some(0));
+    }
+}
@@ -0,0 +1,140 @@
+use serde::{ Serialize, Deserialize, Deserializer, Serializer };
+
+use std::fmt;
+use std::ops::BitOr;
+
+/// Represents a set of OAuth scopes the user is asked to approve
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Scope(u8);
+
+/// Every known scope with its name on Spotify and a readable description
+const SCOPES: [(Scope, &str, &str); 5] = [
+    (Scope::READ_PRIVATE, "user-read-private", "Read your account details"),
+    (Scope::READ_PLAYBACK, "user-read-playback-state", "Read what you are currently playing"),
+    (Scope::RECENTLY_PLAYED, "user-read-recently-played", "Read your recently played tracks"),
+    (Scope::LIBRARY, "user-library-read", "Read your saved albums"),
+    (Scope::MODIFY_PLAYBACK, "user-modify-playback-state", "Control playback on your devices"),
+];
+
+/// Scope requested before scopes were stored alongside the refresh token
+const LEGACY_SCOPE: &str = "user-read-private user-read-email user-read-playback-state";
+
+impl Scope {
+    pub const NONE: Scope = Scope(0);
+    pub const READ_PRIVATE: Scope = Scope(1);
+    pub const READ_PLAYBACK: Scope = Scope(1 << 1);
+    pub const RECENTLY_PLAYED: Scope = Scope(1 << 2);
+    pub const LIBRARY: Scope = Scope(1 << 3);
+    pub const MODIFY_PLAYBACK: Scope = Scope(1 << 4);
+
+    /// Scope needed to run the enabled features
+    pub fn for_features(features: &Features) -> Scope {
+        let mut scope = Scope::READ_PRIVATE;
+        if features.playback {
+            scope = scope | Scope::READ_PLAYBACK;
+        }
+        if features.recently_played {
+            scope = scope | Scope::RECENTLY_PLAYED;
+        }
+        if features.library {
+            scope = scope | Scope::LIBRARY;
+        }
+        if features.playback_control {
+            scope = scope | Scope::MODIFY_PLAYBACK;
+        }
+        scope
+    }
+
+    /// Scope granted to tokens created before scopes were stored
+    pub fn legacy() -> Scope {
+        Scope::parse(LEGACY_SCOPE)
+    }
+
+    /// Parses a space separated scope list, ignoring scopes that are not used
+    pub fn parse(value: &str) -> Scope {
+        value
+            .split_whitespace()
+            .filter_map(|name| SCOPES.iter().find(|(_, known, _)| *known == name))
+            .fold(Scope::NONE, |scope, (flag, _, _)| scope | *flag)
+    }
+
+    pub fn contains(&self, other: Scope) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Scopes in `required` that this set does not grant
+    pub fn missing(&self, required: Scope) -> Scope {
+        Scope(required.0 & !self.0)
+    }
+
+    /// Readable descriptions of each scope in the set
+    pub fn descriptions(&self) -> Vec<&'static str> {
+        SCOPES
+            .iter()
+            .filter(|(flag, _, _)| self.contains(*flag))
+            .map(|(_, _, description)| *description)
+            .collect()
+    }
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Scope::READ_PRIVATE | Scope::READ_PLAYBACK
+    }
+}
+
+impl BitOr for Scope {
+    type Output = Scope;
+
+    fn bitor(self, rhs: Scope) -> Scope {
+        Scope(self.0 | rhs.0)
+    }
+}
+
+/// Formats the set as the space separated list expected by the authorize endpoint
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = SCOPES
+            .iter()
+            .filter(|(flag, _, _)| self.contains(*flag))
+            .map(|(_, name, _)| *name)
+            .collect();
+        write!(f, "{}", names.join(" "))
+    }
+}
+
+impl Serialize for Scope {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Scope {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Scope::parse(&String::deserialize(deserializer)?))
+    }
+}
+
+/// Represents the screensaver features that need extra permissions
+#[derive(Clone, Copy, Debug)]
+pub struct Features {
+    /// Show the currently playing item
+    pub playback: bool,
+    /// Show recently played covers while nothing is playing
+    pub recently_played: bool,
+    /// Show saved albums while nothing is playing
+    pub library: bool,
+    /// Control playback from the screensaver
+    pub playback_control: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            playback: true,
+            recently_played: true,
+            library: false,
+            playback_control: false,
+        }
+    }
+}
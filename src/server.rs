@@ -0,0 +1,317 @@
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use serde_json::json;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    select,
+    sync::broadcast,
+    time::sleep,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+use std::sync::{Arc, RwLock};
+
+use crate::artwork::ArtworkCache;
+use crate::events::{PlaybackEvent, Poller, DEFAULT_INTERVAL};
+use crate::history::HistoryLogger;
+use crate::hooks::Hooks;
+use crate::playback::PlaybackState;
+use crate::settings::ServerSettings;
+use crate::source::NowPlayingSource;
+
+/// Port the server listens on by default, one above the sign in callback
+pub const DEFAULT_PORT: u16 = 8001;
+/// Side in pixels of the cover that is served
+const COVER_SIDE: u32 = 640;
+
+/// Playback state shared between the poller and the connections
+#[derive(Default)]
+struct Current {
+    playback: Option<PlaybackState>,
+    cover: Option<Bytes>,
+}
+
+/// Serves playback from `source` on the loopback interface until Ctrl+C is pressed
+///
+/// Only requests addressed to this port on the loopback interface are answered, and browsers may
+/// only read from local pages and the origins allowed in `settings`
+///
+/// - `GET /now-playing` answers with the player state as json, or `null` when nothing is playing
+/// - `GET /cover` answers with the current cover image
+/// - `/events` upgrades to a WebSocket that receives the current state, then every change event
+///   as json and every new cover as a binary message
+pub async fn serve(
+    source: impl NowPlayingSource,
+    port: u16,
+    settings: ServerSettings,
+    hooks: Hooks,
+    mut history: Option<HistoryLogger>,
+) {
+    let listener = match TcpListener::bind(("127.0.0.1", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("Could not listen on port {port}: {e}");
+            return;
+        }
+    };
+    println!("Serving now playing on http://127.0.0.1:{}/now-playing", port);
+
+    let current = Arc::new(RwLock::new(Current::default()));
+    let (pushes, _) = broadcast::channel::<Message>(32);
+    let origins: Arc<[String]> = settings.allowed_origins.into();
+    tokio::spawn(accept(listener, port, current.clone(), pushes.clone(), origins));
+
+    let mut cache = ArtworkCache::open_default().expect("Could not open artwork cache");
+    let mut poller = Poller::new(source, DEFAULT_INTERVAL);
+    let shutdown = tokio::signal::ctrl_c();
+    futures::pin_mut!(shutdown);
+    loop {
+        match poller.poll().await {
+            Ok(events) => {
+                current.write().expect("Connection panicked").playback = poller.state().cloned();
+                for event in events {
+                    hooks.on_event(&event);
+                    if let Some(history) = &mut history {
+                        history.on_event(&event);
+                    }
+                    let images = event.item().map(|item| item.images().to_vec());
+                    // Nobody listening is not an error
+                    let _ = pushes.send(Message::Text(serde_json::to_string(&event).expect("Could not convert")));
+
+                    if let Some(images) = images {
+                        let cover = cache.fetch_best(&images, COVER_SIDE).await;
+                        if let Some(cover) = &cover {
+                            let _ = pushes.send(Message::Binary(cover.to_vec()));
+                        }
+                        current.write().expect("Connection panicked").cover = cover;
+                    } else if matches!(event, PlaybackEvent::Stopped) {
+                        current.write().expect("Connection panicked").cover = None;
+                    }
+                }
+            }
+            Err(e) => println!("Could not fetch playback: {e}"),
+        }
+        select! {
+            _ = sleep(poller.interval()) => {}
+            _ = &mut shutdown => break,
+        }
+    }
+    // The item still playing would otherwise be lost
+    if let Some(history) = history {
+        history.close();
+    }
+}
+
+/// Accepts connections forever, handling each on its own task
+async fn accept(
+    listener: TcpListener,
+    port: u16,
+    current: Arc<RwLock<Current>>,
+    pushes: broadcast::Sender<Message>,
+    origins: Arc<[String]>,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _addr)) => {
+                let current = current.clone();
+                let pushes = pushes.subscribe();
+                let origins = origins.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle(stream, port, current, pushes, &origins).await {
+                        println!("Connection failed: {e}");
+                    }
+                });
+            }
+            Err(e) => println!("Could not accept connection: {e}"),
+        }
+    }
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    port: u16,
+    current: Arc<RwLock<Current>>,
+    pushes: broadcast::Receiver<Message>,
+    origins: &[String],
+) -> std::io::Result<()> {
+    let mut buffer = [0; 2048];
+    // Peek so the WebSocket handshake can still read the request
+    let read = stream.peek(&mut buffer).await?;
+    let request = String::from_utf8_lossy(&buffer[..read]).to_string();
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let origin = header(&request, "origin");
+
+    // A page could otherwise rebind its own name to 127.0.0.1, and any page open in a browser
+    // could read what is playing
+    let refusal: Option<&[u8]> = if !local_host(header(&request, "host"), port) {
+        Some(b"Host not allowed")
+    } else if !allowed(origin, origins) {
+        Some(b"Origin not allowed")
+    } else {
+        None
+    };
+    if let Some(body) = refusal {
+        let _ = stream.read(&mut buffer).await?;
+        let header = format!(
+            "HTTP/1.1 403 Forbidden\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(header.as_bytes()).await?;
+        stream.write_all(body).await?;
+        return stream.flush().await;
+    }
+
+    if path == "/events" && request.to_ascii_lowercase().contains("upgrade: websocket") {
+        return push_events(stream, current, pushes).await;
+    }
+    let _ = stream.read(&mut buffer).await?;
+
+    let (status_line, content_type, body) = match path {
+        "/" | "/now-playing" => {
+            let playback = &current.read().expect("Poller panicked").playback;
+            let body = serde_json::to_vec(playback).expect("Could not convert");
+            ("HTTP/1.1 200 OK", "application/json", body)
+        }
+        "/cover" => match &current.read().expect("Poller panicked").cover {
+            Some(cover) => ("HTTP/1.1 200 OK", image_type(cover), cover.to_vec()),
+            None => ("HTTP/1.1 404 Not Found", "text/plain", b"Nothing is playing".to_vec()),
+        },
+        _ => ("HTTP/1.1 404 Not Found", "text/plain", b"Not found".to_vec()),
+    };
+
+    // Browser sources and dashboards are served from other origins, answer only the one asking
+    let cors = origin.map_or(String::new(), |origin| {
+        format!("Access-Control-Allow-Origin: {origin}\r\nVary: Origin\r\n")
+    });
+    let header = format!(
+        "{status_line}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n{cors}Connection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.flush().await
+}
+
+/// Completes the WebSocket handshake, then forwards pushes until the client goes away
+async fn push_events(
+    stream: TcpStream,
+    current: Arc<RwLock<Current>>,
+    mut pushes: broadcast::Receiver<Message>,
+) -> std::io::Result<()> {
+    let mut socket = tokio_tungstenite::accept_async(stream).await.map_err(std::io::Error::other)?;
+
+    let (state, cover) = {
+        let current = current.read().expect("Poller panicked");
+        (json!({ "event": "state", "data": current.playback }).to_string(), current.cover.clone())
+    };
+    socket.send(Message::Text(state)).await.map_err(std::io::Error::other)?;
+    if let Some(cover) = cover {
+        socket.send(Message::Binary(cover.to_vec())).await.map_err(std::io::Error::other)?;
+    }
+
+    loop {
+        select! {
+            push = pushes.recv() => match push {
+                Ok(message) => socket.send(message).await.map_err(std::io::Error::other)?,
+                // Slow clients miss some pushes rather than holding the others back
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            incoming = socket.next() => match incoming {
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(std::io::Error::other(e)),
+            },
+        }
+    }
+}
+
+/// Value of the header `name` in a raw request, matched case insensitively
+fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    request
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+/// Whether a request addressed to `host` was meant for this server on `port`
+fn local_host(host: Option<&str>, port: u16) -> bool {
+    host.is_some_and(|host| host == format!("127.0.0.1:{port}") || host == format!("localhost:{port}"))
+}
+
+/// Whether a request from `origin` may be answered
+///
+/// Requests without an origin come from tools rather than web pages. Pages served from this
+/// machine are trusted too, while `null` origins from sandboxed or local file pages must be listed
+fn allowed(origin: Option<&str>, origins: &[String]) -> bool {
+    let Some(origin) = origin else { return true };
+    if origins.iter().any(|allowed| allowed.trim_end_matches('/') == origin) {
+        return true;
+    }
+    reqwest::Url::parse(origin).is_ok_and(|url| {
+        matches!(url.scheme(), "http" | "https")
+            && matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"))
+    })
+}
+
+/// MIME type of an encoded image, from its first bytes
+fn image_type(image: &[u8]) -> &'static str {
+    match image {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        _ => "image/jpeg",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_local_and_allowed_origins_are_answered() {
+        let origins = vec![String::from("https://dashboard.example/")];
+        let cases = [
+            (None, true),
+            (Some("null"), false),
+            (Some("http://localhost:3000"), true),
+            (Some("http://127.0.0.1"), true),
+            (Some("http://[::1]:8080"), true),
+            (Some("https://dashboard.example"), true),
+            (Some("https://evil.example"), false),
+            (Some("http://localhost.evil.example"), false),
+            (Some("file://localhost"), false),
+            (Some("not an origin"), false),
+        ];
+        for (origin, expected) in cases {
+            assert_eq!(allowed(origin, &origins), expected, "{origin:?}");
+        }
+        assert!(allowed(Some("null"), &[String::from("null")]));
+    }
+
+    #[test]
+    fn only_requests_to_this_port_are_answered() {
+        let cases = [
+            (Some("127.0.0.1:8001"), true),
+            (Some("localhost:8001"), true),
+            (Some("127.0.0.1:8080"), false),
+            (Some("localhost"), false),
+            (Some("evil.example:8001"), false),
+            (Some("127.0.0.1.evil.example:8001"), false),
+            (None, false),
+        ];
+        for (host, expected) in cases {
+            assert_eq!(local_host(host, 8001), expected, "{host:?}");
+        }
+    }
+
+    #[test]
+    fn headers_are_found_in_any_case() {
+        let request = "GET /events HTTP/1.1\r\nHost: 127.0.0.1\r\nORIGIN: null\r\n\r\norigin: body";
+        assert_eq!(header(request, "origin"), Some("null"));
+        assert_eq!(header(request, "upgrade"), None);
+    }
+}
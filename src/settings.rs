@@ -0,0 +1,453 @@
+use serde::{ Serialize, Deserialize };
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+pub const SETTINGS_FILE: &str = "settings.json";
+/// Version of the settings.json layout, raised whenever a setting changes meaning
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// Represents settings.json, stored next to user.json and constants.json
+///
+/// The installer copies it into the built saver, so the native savers read the same file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Settings {
+    /// Layout the file was written with, see [`SETTINGS_VERSION`]
+    pub version: u32,
+    pub hooks: HookSettings,
+    pub history: HistorySettings,
+    pub server: ServerSettings,
+    pub saver: SaverSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            hooks: HookSettings::default(),
+            history: HistorySettings::default(),
+            server: ServerSettings::default(),
+            saver: SaverSettings::default(),
+        }
+    }
+}
+
+/// Represents commands run when the playing track changes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HookSettings {
+    /// Shell commands to run, track details are passed as `SPOTIFY_*` environment variables
+    pub commands: Vec<String>,
+    /// FIFO to write a json line to on every change, skipped while nothing is reading it
+    pub fifo: Option<PathBuf>,
+    /// Seconds a command may run before it is killed
+    pub timeout_secs: u64,
+    /// Commands allowed to run at once, changes arriving while all are busy are skipped
+    pub max_concurrent: usize,
+    /// Whether the screensaver runs hooks too, or only `watch`
+    pub in_saver: bool,
+}
+
+impl Default for HookSettings {
+    fn default() -> Self {
+        Self {
+            commands: Vec::new(),
+            fifo: None,
+            timeout_secs: 5,
+            max_concurrent: 2,
+            in_saver: true,
+        }
+    }
+}
+
+/// Represents the listening history log
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HistorySettings {
+    /// Whether played tracks and episodes are recorded
+    pub enabled: bool,
+    /// CSV file the history is appended to
+    pub path: PathBuf,
+    /// Which command writes the history, so running several does not record each play twice
+    pub writer: HistoryWriter,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: PathBuf::from("history.csv"),
+            writer: HistoryWriter::Saver,
+        }
+    }
+}
+
+/// Represents the command that records the listening history
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryWriter {
+    /// The screensaver, while it is shown
+    Saver,
+    /// `spotify_user watch`
+    Watch,
+    /// `spotify_user serve`
+    Serve,
+}
+
+/// Represents who may read from `spotify_user serve`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ServerSettings {
+    /// Web origins allowed besides local pages and OBS, such as `https://dashboard.example`
+    pub allowed_origins: Vec<String>,
+}
+
+/// Represents how the screensaver's cover moves
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SaverSettings {
+    /// Side of the cover as a fraction of the shorter side of the screen
+    pub size: f32,
+    /// Pixels per second the cover moves at
+    pub speed: f32,
+    /// Starting direction in degrees clockwise from the right, random if unset
+    pub angle: Option<f32>,
+    /// Seed for the starting position and direction, so runs can be repeated
+    pub seed: Option<u64>,
+    /// How many pixels off a corner a bounce may land and still count as hitting it
+    pub corner_tolerance: f32,
+    /// What happens on screen when the cover hits a corner
+    pub celebration: Celebration,
+    /// Shows which corner the cover will hit next and when
+    pub predict_corners: bool,
+    /// Text shown with the cover
+    pub info: InfoSettings,
+    /// Shows how far into the item playback is along the bottom of the cover
+    pub progress_bar: bool,
+    /// Shows the elapsed and remaining time above the progress bar
+    pub progress_times: bool,
+    /// What is colored after the cover
+    pub tint: Tint,
+    /// Leaves fading copies of the cover behind it
+    pub trail: bool,
+    /// How one cover turns into the next
+    pub transition: TransitionSettings,
+    /// Smaller covers bouncing alongside the playing one
+    pub extras: ExtraCovers,
+    /// How the cover is spread over several monitors
+    pub displays: DisplayMode,
+    /// What is shown while nothing is playing
+    pub idle: IdleScene,
+    /// How often playback is read
+    pub poll_interval_ms: u64,
+    /// Lets the space bar, arrows and media keys control playback instead of closing the screensaver
+    pub interactive: bool,
+    /// Shows which device is playing in the corner of the screen
+    pub show_device: bool,
+}
+
+/// Represents what the screensaver shows while nothing is playing
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleScene {
+    /// The covers of the most recently played tracks, one after the other
+    Recent,
+    /// The covers of the albums saved to the library, one after the other
+    Albums,
+    /// The time bouncing in place of the cover
+    Clock,
+    /// The day and date bouncing in place of the cover
+    Date,
+    /// A slowly shifting gradient and nothing else
+    Ambient,
+}
+
+impl IdleScene {
+    pub const ALL: [IdleScene; 5] = [IdleScene::Recent, IdleScene::Albums, IdleScene::Clock, IdleScene::Date, IdleScene::Ambient];
+
+    /// Whether the scene cycles through covers fetched from the account
+    pub fn shows_covers(self) -> bool {
+        matches!(self, IdleScene::Recent | IdleScene::Albums)
+    }
+}
+
+impl fmt::Display for IdleScene {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IdleScene::Recent => "Recently played covers",
+            IdleScene::Albums => "Saved album covers",
+            IdleScene::Clock => "Clock",
+            IdleScene::Date => "Date",
+            IdleScene::Ambient => "Ambient gradient",
+        })
+    }
+}
+
+/// Represents how the screensaver uses several monitors
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayMode {
+    /// Every monitor has a cover of its own bouncing around it
+    Each,
+    /// One cover bounces across all monitors as if they were one screen
+    Span,
+    /// The cover only bounces on the primary monitor, the others are blank
+    Primary,
+}
+
+impl DisplayMode {
+    pub const ALL: [DisplayMode; 3] = [DisplayMode::Each, DisplayMode::Span, DisplayMode::Primary];
+}
+
+impl fmt::Display for DisplayMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DisplayMode::Each => "A cover on each display",
+            DisplayMode::Span => "One cover across all displays",
+            DisplayMode::Primary => "Primary display only",
+        })
+    }
+}
+
+/// Represents the smaller covers bouncing alongside the playing one
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ExtraCovers {
+    pub from: ExtraSource,
+    /// Most covers shown at once
+    pub count: usize,
+    /// Side of each cover as a fraction of the shorter side of the screen
+    pub size: f32,
+}
+
+impl Default for ExtraCovers {
+    fn default() -> Self {
+        Self {
+            from: ExtraSource::None,
+            count: 3,
+            size: 0.12,
+        }
+    }
+}
+
+/// Represents where the extra covers come from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtraSource {
+    None,
+    /// The items that will play next
+    Queue,
+    /// The most recently played tracks
+    Recent,
+    /// The queue, topped up with recently played tracks
+    Both,
+}
+
+/// Represents the animation played when the cover changes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct TransitionSettings {
+    pub style: TransitionStyle,
+    pub duration_ms: u64,
+    pub easing: Easing,
+}
+
+impl Default for TransitionSettings {
+    fn default() -> Self {
+        Self {
+            style: TransitionStyle::Crossfade,
+            duration_ms: 600,
+            easing: Easing::EaseInOut,
+        }
+    }
+}
+
+/// Represents how the previous cover turns into the next
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransitionStyle {
+    /// The new cover replaces the previous one at once
+    None,
+    Crossfade,
+    /// The cover turns over like a card
+    Flip,
+    /// The new cover pushes the previous one out sideways
+    Slide,
+    /// The previous cover breaks up into tiles that vanish one by one
+    Dissolve,
+}
+
+impl TransitionStyle {
+    pub const ALL: [TransitionStyle; 5] = [
+        TransitionStyle::None,
+        TransitionStyle::Crossfade,
+        TransitionStyle::Flip,
+        TransitionStyle::Slide,
+        TransitionStyle::Dissolve,
+    ];
+}
+
+impl fmt::Display for TransitionStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TransitionStyle::None => "None",
+            TransitionStyle::Crossfade => "Crossfade",
+            TransitionStyle::Flip => "Flip",
+            TransitionStyle::Slide => "Slide",
+            TransitionStyle::Dissolve => "Dissolve",
+        })
+    }
+}
+
+/// Represents how a transition speeds up and slows down
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps linear progress from 0 to 1 onto this curve
+    pub fn apply(self, progress: f32) -> f32 {
+        let t = progress.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::EaseInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+        }
+    }
+}
+
+/// Represents which parts of the screensaver take their colors from the cover
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Tint {
+    /// Black background and white details
+    None,
+    /// The background takes the cover's dominant color
+    Background,
+    /// The text, progress bar and trail take the cover's accent color
+    Accents,
+    /// Both the background and the details
+    Full,
+}
+
+impl fmt::Display for Tint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Tint::None => "Black and white",
+            Tint::Background => "Background from the cover",
+            Tint::Accents => "Details from the cover",
+            Tint::Full => "Background and details from the cover",
+        })
+    }
+}
+
+impl Tint {
+    pub const ALL: [Tint; 4] = [Tint::None, Tint::Background, Tint::Accents, Tint::Full];
+
+    pub fn background(self) -> bool {
+        matches!(self, Tint::Background | Tint::Full)
+    }
+
+    pub fn accents(self) -> bool {
+        matches!(self, Tint::Accents | Tint::Full)
+    }
+}
+
+/// Represents the track details drawn with the cover
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct InfoSettings {
+    /// Whether the title, artists and album are drawn
+    pub show: bool,
+    /// Draws the text above the cover instead of beneath it
+    pub above: bool,
+    /// Name of an installed font family, the default font if unset
+    pub font: Option<String>,
+    /// Text size in pixels
+    pub size: f32,
+    /// Hex color such as `#ffffff`
+    pub color: String,
+}
+
+impl Default for InfoSettings {
+    fn default() -> Self {
+        Self {
+            show: true,
+            above: false,
+            font: None,
+            size: 18.0,
+            color: String::from("#ffffff"),
+        }
+    }
+}
+
+/// Represents the effect played when the cover hits a corner
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Celebration {
+    None,
+    /// The screen flashes white
+    Flash,
+    /// Confetti bursts out of the corner
+    Confetti,
+    /// The cover briefly grows and shrinks back
+    Pulse,
+}
+
+impl Default for SaverSettings {
+    fn default() -> Self {
+        Self {
+            size: 0.25,
+            speed: 170.0,
+            angle: None,
+            seed: None,
+            corner_tolerance: 4.0,
+            celebration: Celebration::Confetti,
+            predict_corners: false,
+            info: InfoSettings::default(),
+            progress_bar: true,
+            progress_times: false,
+            tint: Tint::Background,
+            trail: false,
+            transition: TransitionSettings::default(),
+            extras: ExtraCovers::default(),
+            displays: DisplayMode::Each,
+            idle: IdleScene::Recent,
+            poll_interval_ms: 2000,
+            interactive: false,
+            show_device: true,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads settings.json, falling back to the defaults if it is missing or invalid
+    pub fn load() -> Settings {
+        let settings = match fs::read_to_string(SETTINGS_FILE) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                println!("Could not read {}, using defaults: {}", SETTINGS_FILE, e);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        };
+        if settings.version > SETTINGS_VERSION {
+            println!("{} was written by a newer version, some settings may be ignored", SETTINGS_FILE);
+        }
+        settings
+    }
+
+    /// Writes settings.json in the current layout
+    pub fn save(&self) -> std::io::Result<()> {
+        let settings = Settings { version: SETTINGS_VERSION, ..self.clone() };
+        fs::write(SETTINGS_FILE, serde_json::to_string_pretty(&settings).expect("Could not convert"))
+    }
+}
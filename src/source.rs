@@ -0,0 +1,523 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::playback::{Album, Device, PlayHistory, PlaybackCommand, PlaybackState, PlayingItem};
+use crate::scope::Scope;
+use crate::settings::IdleScene;
+use crate::spotify::{SpotifyError, SpotifyUser};
+
+/// Represents an error returned by a [`NowPlayingSource`]
+#[derive(Debug)]
+pub enum SourceError {
+    Spotify(SpotifyError),
+    /// The access token expired and could not be refreshed
+    AuthLost(SpotifyError),
+    /// The source cannot be used on this platform, or cannot do what was asked
+    Unavailable(&'static str),
+    #[cfg(target_os = "linux")]
+    Mpris(zbus::Error),
+    /// The mock script could not be read
+    File(std::io::Error),
+    /// The mock script is not valid json of the expected form
+    Script(serde_json::Error),
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::Spotify(e) => write!(f, "{}", e),
+            SourceError::AuthLost(e) => write!(f, "login lost: {}", e),
+            SourceError::Unavailable(reason) => write!(f, "{}", reason),
+            #[cfg(target_os = "linux")]
+            SourceError::Mpris(e) => write!(f, "D-Bus error: {}", e),
+            SourceError::File(e) => write!(f, "could not read mock script: {}", e),
+            SourceError::Script(e) => write!(f, "invalid mock script: {}", e),
+        }
+    }
+}
+
+impl From<SpotifyError> for SourceError {
+    fn from(e: SpotifyError) -> Self {
+        SourceError::Spotify(e)
+    }
+}
+
+/// Represents anything that can report what the user is listening to
+pub trait NowPlayingSource {
+    /// Current playback state, `None` when nothing is playing
+    async fn current_playback(&mut self) -> Result<Option<PlaybackState>, SourceError>;
+
+    /// Up to `limit` of the most recently played tracks, newest first
+    ///
+    /// Sources without a listening history return an empty list
+    async fn recently_played(&mut self, _limit: u8) -> Result<Vec<PlayHistory>, SourceError> {
+        Ok(Vec::new())
+    }
+
+    /// Up to `limit` of the items that will play next, in order
+    ///
+    /// Sources that cannot see the queue return an empty list
+    async fn queue(&mut self, _limit: usize) -> Result<Vec<PlayingItem>, SourceError> {
+        Ok(Vec::new())
+    }
+
+    /// Up to `limit` of the albums saved to the library, most recently saved first
+    ///
+    /// Sources without a library return an empty list
+    async fn saved_albums(&mut self, _limit: u8) -> Result<Vec<Album>, SourceError> {
+        Ok(Vec::new())
+    }
+
+    /// Asks the player to carry out `command`
+    async fn control(&mut self, _command: PlaybackCommand) -> Result<(), SourceError> {
+        Err(SourceError::Unavailable("this source cannot control playback"))
+    }
+}
+
+/// Represents which source the renderer should read playback from
+#[derive(Clone, Debug)]
+pub enum SourceKind {
+    /// The Spotify Web API, using the credentials in user.json and constants.json
+    Spotify,
+    /// A local player over MPRIS, holding the player's bus name suffix
+    Mpris(String),
+    /// A scripted list of playback states read from a json file
+    Mock(String),
+}
+
+impl SourceKind {
+    /// Parses a `--source` argument: `spotify`, `mpris`, `mpris:<player>` or `mock:<file>`
+    pub fn parse(value: &str) -> Option<SourceKind> {
+        match value.split_once(':') {
+            None if value == "spotify" => Some(SourceKind::Spotify),
+            None if value == "mpris" => Some(SourceKind::Mpris(String::from("spotify"))),
+            Some(("mpris", player)) => Some(SourceKind::Mpris(player.to_string())),
+            Some(("mock", path)) => Some(SourceKind::Mock(path.to_string())),
+            _ => None,
+        }
+    }
+}
+
+impl SourceKind {
+    /// Connects to the source this kind describes, `idle` is the saver's idle scene and `None` elsewhere
+    pub async fn connect(self, idle: Option<IdleScene>) -> Result<AnySource, SourceError> {
+        match self {
+            SourceKind::Spotify => Ok(AnySource::Spotify(SpotifySource::connect(SpotifyUser::granted_scope(), idle).await?)),
+            SourceKind::Mock(path) => Ok(AnySource::Mock(MockSource::from_file(path)?)),
+            #[cfg(target_os = "linux")]
+            SourceKind::Mpris(player) => Ok(AnySource::Mpris(MprisSource::connect(&player).await?)),
+            #[cfg(not(target_os = "linux"))]
+            SourceKind::Mpris(_) => Err(SourceError::Unavailable("MPRIS is only available on Linux")),
+        }
+    }
+}
+
+/// Any of the sources a [`SourceKind`] can describe, chosen at runtime
+pub enum AnySource {
+    Spotify(SpotifySource),
+    Mock(MockSource),
+    #[cfg(target_os = "linux")]
+    Mpris(MprisSource),
+}
+
+impl NowPlayingSource for AnySource {
+    async fn current_playback(&mut self) -> Result<Option<PlaybackState>, SourceError> {
+        match self {
+            AnySource::Spotify(source) => source.current_playback().await,
+            AnySource::Mock(source) => source.current_playback().await,
+            #[cfg(target_os = "linux")]
+            AnySource::Mpris(source) => source.current_playback().await,
+        }
+    }
+
+    async fn recently_played(&mut self, limit: u8) -> Result<Vec<PlayHistory>, SourceError> {
+        match self {
+            AnySource::Spotify(source) => source.recently_played(limit).await,
+            AnySource::Mock(source) => source.recently_played(limit).await,
+            #[cfg(target_os = "linux")]
+            AnySource::Mpris(source) => source.recently_played(limit).await,
+        }
+    }
+
+    async fn queue(&mut self, limit: usize) -> Result<Vec<PlayingItem>, SourceError> {
+        match self {
+            AnySource::Spotify(source) => source.queue(limit).await,
+            AnySource::Mock(source) => source.queue(limit).await,
+            #[cfg(target_os = "linux")]
+            AnySource::Mpris(source) => source.queue(limit).await,
+        }
+    }
+
+    async fn saved_albums(&mut self, limit: u8) -> Result<Vec<Album>, SourceError> {
+        match self {
+            AnySource::Spotify(source) => source.saved_albums(limit).await,
+            AnySource::Mock(source) => source.saved_albums(limit).await,
+            #[cfg(target_os = "linux")]
+            AnySource::Mpris(source) => source.saved_albums(limit).await,
+        }
+    }
+
+    async fn control(&mut self, command: PlaybackCommand) -> Result<(), SourceError> {
+        match self {
+            AnySource::Spotify(source) => source.control(command).await,
+            AnySource::Mock(source) => source.control(command).await,
+            #[cfg(target_os = "linux")]
+            AnySource::Mpris(source) => source.control(command).await,
+        }
+    }
+}
+
+/// Reads playback from the Spotify Web API
+pub struct SpotifySource {
+    client: SpotifyUser,
+    /// Whether the stored token was granted access to the listening history
+    can_read_recent: bool,
+    /// Whether the stored token was granted access to the saved albums
+    can_read_library: bool,
+    /// Whether the stored token was granted control over playback
+    can_control: bool,
+}
+
+impl SpotifySource {
+    /// Creates a client from constants.json and generates its first access token
+    ///
+    /// `scope` is what the saved login was granted, and `idle` the saver's idle scene or `None` elsewhere
+    pub async fn connect(scope: Option<Scope>, idle: Option<IdleScene>) -> Result<SpotifySource, SourceError> {
+        let mut client = SpotifyUser::from_constants()?;
+        match client.refresh_token().await {
+            Ok(()) => {}
+            // Without a readable login every request fails, so the saver reports the login as lost
+            Err(e @ (SpotifyError::File(..) | SpotifyError::Parse(_))) => {
+                println!("Saved login could not be used: {e}, sign in again through the installer");
+            }
+            Err(e) => println!("Could not generate token: {e}"),
+        }
+
+        let granted = |wanted| scope.is_some_and(|scope| scope.contains(wanted));
+        // Tokens approved before recently played was requested cannot read it
+        let can_read_recent = granted(Scope::RECENTLY_PLAYED);
+        if !can_read_recent {
+            println!("Saved login cannot read recently played tracks, sign in again to enable idle covers");
+        }
+
+        let can_read_library = granted(Scope::LIBRARY);
+        if !can_read_library && idle == Some(IdleScene::Albums) {
+            println!("Saved login cannot read saved albums, sign in again with saved albums enabled");
+        }
+
+        let can_control = granted(Scope::MODIFY_PLAYBACK);
+
+        Ok(SpotifySource { client, can_read_recent, can_read_library, can_control })
+    }
+
+    /// Lists the devices the account can play on
+    pub async fn devices(&mut self) -> Result<Vec<Device>, SourceError> {
+        self.retry_unauthorized(async |client| client.devices().await).await
+    }
+
+    /// Carries out `command` on the device with ID `device`, or the active device if `None`
+    pub async fn control_device(&mut self, command: &PlaybackCommand, device: Option<&str>) -> Result<(), SourceError> {
+        if !self.can_control {
+            return Err(SourceError::Unavailable("saved login cannot control playback, sign in again with playback controls allowed"));
+        }
+        self.retry_unauthorized(async |client| match command {
+            PlaybackCommand::Play => client.play(device).await,
+            PlaybackCommand::Pause => client.pause(device).await,
+            PlaybackCommand::Next => client.next(device).await,
+            PlaybackCommand::Previous => client.previous(device).await,
+            PlaybackCommand::Seek(position_ms) => client.seek(*position_ms, device).await,
+            PlaybackCommand::Volume(percent) => client.set_volume(*percent, device).await,
+            PlaybackCommand::Shuffle(shuffle) => client.set_shuffle(*shuffle, device).await,
+            PlaybackCommand::Repeat(mode) => client.set_repeat(*mode, device).await,
+            PlaybackCommand::Transfer { device, play } => client.transfer_playback(device, *play).await,
+            PlaybackCommand::AddToQueue(uri) => client.add_to_queue(uri, device).await,
+        }).await
+    }
+
+    /// Runs `request` again with a new access token if the current one expired
+    async fn retry_unauthorized<T>(&mut self, request: impl AsyncFn(&SpotifyUser) -> Result<T, SpotifyError>) -> Result<T, SourceError> {
+        match request(&self.client).await {
+            Err(SpotifyError::Unauthorized) => {
+                self.client.refresh_token().await.map_err(SourceError::AuthLost)?;
+                match request(&self.client).await {
+                    Err(SpotifyError::Unauthorized) => Err(SourceError::AuthLost(SpotifyError::Unauthorized)),
+                    other => Ok(other?),
+                }
+            }
+            other => Ok(other?),
+        }
+    }
+}
+
+impl NowPlayingSource for SpotifySource {
+    async fn current_playback(&mut self) -> Result<Option<PlaybackState>, SourceError> {
+        self.retry_unauthorized(async |client| client.current_playback().await).await
+    }
+
+    async fn recently_played(&mut self, limit: u8) -> Result<Vec<PlayHistory>, SourceError> {
+        if !self.can_read_recent {
+            return Ok(Vec::new());
+        }
+        self.retry_unauthorized(async |client| client.recently_played(limit).await).await
+    }
+
+    async fn queue(&mut self, limit: usize) -> Result<Vec<PlayingItem>, SourceError> {
+        let mut queue = self.retry_unauthorized(async |client| client.queue().await).await?;
+        queue.truncate(limit);
+        Ok(queue)
+    }
+
+    async fn saved_albums(&mut self, limit: u8) -> Result<Vec<Album>, SourceError> {
+        if !self.can_read_library {
+            return Ok(Vec::new());
+        }
+        self.retry_unauthorized(async |client| client.saved_albums(limit).await).await
+    }
+
+    async fn control(&mut self, command: PlaybackCommand) -> Result<(), SourceError> {
+        self.control_device(&command, None).await
+    }
+}
+
+/// Replays a fixed list of playback states, one per poll, repeating the last one forever
+///
+/// Lets the renderer run without a network connection or a Spotify account
+pub struct MockSource {
+    steps: VecDeque<Option<PlaybackState>>,
+    recent: Vec<PlayHistory>,
+    queue: Vec<PlayingItem>,
+    albums: Vec<Album>,
+}
+
+/// Represents the json file a [`MockSource`] is read from
+#[derive(serde::Deserialize)]
+struct MockScript {
+    /// Playback state for each poll, `null` when nothing is playing
+    steps: Vec<Option<PlaybackState>>,
+    #[serde(default)]
+    recent: Vec<PlayHistory>,
+    #[serde(default)]
+    queue: Vec<PlayingItem>,
+    #[serde(default)]
+    albums: Vec<Album>,
+}
+
+impl MockSource {
+    pub fn new(steps: Vec<Option<PlaybackState>>, recent: Vec<PlayHistory>, queue: Vec<PlayingItem>, albums: Vec<Album>) -> MockSource {
+        MockSource {
+            steps: steps.into(),
+            recent,
+            queue,
+            albums,
+        }
+    }
+
+    /// Reads a script of the form `{"steps": [...], "recent": [...], "queue": [...], "albums": [...]}`
+    pub fn from_file(path: impl AsRef<Path>) -> Result<MockSource, SourceError> {
+        let contents = fs::read_to_string(path).map_err(SourceError::File)?;
+        let script: MockScript = serde_json::from_str(&contents).map_err(SourceError::Script)?;
+        Ok(MockSource::new(script.steps, script.recent, script.queue, script.albums))
+    }
+}
+
+impl NowPlayingSource for MockSource {
+    async fn current_playback(&mut self) -> Result<Option<PlaybackState>, SourceError> {
+        if self.steps.len() > 1 {
+            return Ok(self.steps.pop_front().flatten());
+        }
+        Ok(self.steps.front().cloned().flatten())
+    }
+
+    async fn recently_played(&mut self, limit: u8) -> Result<Vec<PlayHistory>, SourceError> {
+        Ok(self.recent.iter().take(limit as usize).cloned().collect())
+    }
+
+    async fn queue(&mut self, limit: usize) -> Result<Vec<PlayingItem>, SourceError> {
+        Ok(self.queue.iter().take(limit).cloned().collect())
+    }
+
+    async fn saved_albums(&mut self, limit: u8) -> Result<Vec<Album>, SourceError> {
+        Ok(self.albums.iter().take(limit as usize).cloned().collect())
+    }
+
+    /// Only prints the command, the script plays on regardless
+    async fn control(&mut self, command: PlaybackCommand) -> Result<(), SourceError> {
+        println!("Mock playback received {:?}", command);
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use mpris::MprisSource;
+
+#[cfg(target_os = "linux")]
+mod mpris {
+    use std::collections::HashMap;
+
+    use zbus::{fdo, proxy::CacheProperties, zvariant::{Array, OwnedValue}, Connection};
+
+    use super::{NowPlayingSource, SourceError};
+    use crate::playback::{Album, Artist, Image, PlaybackCommand, PlaybackState, PlayingItem, RepeatMode, Track};
+
+    const PATH: &str = "/org/mpris/MediaPlayer2";
+    const INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+    /// Reads playback from a local player, such as the Spotify desktop client, over the D-Bus session bus
+    pub struct MprisSource {
+        connection: Connection,
+        /// Full bus name of the player, e.g. `org.mpris.MediaPlayer2.spotify`
+        destination: String,
+    }
+
+    impl MprisSource {
+        /// Connects to the session bus, `player` is the bus name suffix of the player to read
+        pub async fn connect(player: &str) -> Result<MprisSource, SourceError> {
+            let connection = Connection::session().await.map_err(SourceError::Mpris)?;
+            Ok(MprisSource {
+                connection,
+                destination: format!("org.mpris.MediaPlayer2.{}", player),
+            })
+        }
+
+        async fn proxy(&self) -> zbus::Result<zbus::Proxy<'_>> {
+            zbus::proxy::Builder::new(&self.connection)
+                .destination(self.destination.as_str())?
+                .path(PATH)?
+                .interface(INTERFACE)?
+                .cache_properties(CacheProperties::No)
+                .build()
+                .await
+        }
+
+        async fn read(&self) -> zbus::Result<Option<PlaybackState>> {
+            let proxy = self.proxy().await?;
+
+            let status: String = proxy.get_property("PlaybackStatus").await?;
+            if status == "Stopped" {
+                return Ok(None);
+            }
+            let metadata: HashMap<String, OwnedValue> = proxy.get_property("Metadata").await?;
+            let Some(name) = string(&metadata, "xesam:title") else {
+                return Ok(None);
+            };
+
+            let images = string(&metadata, "mpris:artUrl")
+                .map(|url| vec![Image { url: art_url(url), width: None, height: None }])
+                .unwrap_or_default();
+            let track = Track {
+                uri: string(&metadata, "xesam:url").map(spotify_uri).unwrap_or_default(),
+                name,
+                artists: strings(&metadata, "xesam:artist")
+                    .into_iter()
+                    .map(|name| Artist { name })
+                    .collect(),
+                album: Album {
+                    name: string(&metadata, "xesam:album").unwrap_or_default(),
+                    images,
+                    artists: strings(&metadata, "xesam:albumArtist")
+                        .into_iter()
+                        .map(|name| Artist { name })
+                        .collect(),
+                },
+                duration_ms: length(&metadata).unwrap_or_default(),
+                is_local: false,
+            };
+            let track = Track { is_local: track.uri.starts_with("spotify:local:"), ..track };
+            // Not every player reports its position
+            let position: Option<i64> = proxy.get_property("Position").await.ok();
+            Ok(Some(PlaybackState {
+                item: Some(PlayingItem::Track(track)),
+                is_playing: status == "Playing",
+                progress_ms: position.map(|microseconds| microseconds.max(0) as u64 / 1000),
+                device: None,
+            }))
+        }
+    }
+
+    impl NowPlayingSource for MprisSource {
+        async fn current_playback(&mut self) -> Result<Option<PlaybackState>, SourceError> {
+            match self.read().await {
+                Ok(state) => Ok(state),
+                // The player is not running
+                Err(zbus::Error::FDO(e)) if matches!(*e, fdo::Error::ServiceUnknown(_) | fdo::Error::NameHasNoOwner(_)) => Ok(None),
+                Err(e) => Err(SourceError::Mpris(e)),
+            }
+        }
+
+        async fn control(&mut self, command: PlaybackCommand) -> Result<(), SourceError> {
+            let proxy = self.proxy().await.map_err(SourceError::Mpris)?;
+            let result = match command {
+                PlaybackCommand::Play => proxy.call_method("Play", &()).await,
+                PlaybackCommand::Pause => proxy.call_method("Pause", &()).await,
+                PlaybackCommand::Next => proxy.call_method("Next", &()).await,
+                PlaybackCommand::Previous => proxy.call_method("Previous", &()).await,
+                // MPRIS seeks by an offset in microseconds from the current position
+                PlaybackCommand::Seek(position_ms) => {
+                    let position: i64 = proxy.get_property("Position").await.map_err(SourceError::Mpris)?;
+                    proxy.call_method("Seek", &(position_ms as i64 * 1000 - position)).await
+                }
+                // Properties are set rather than called, and answer without a message
+                PlaybackCommand::Volume(percent) => {
+                    return proxy.set_property("Volume", percent.min(100) as f64 / 100.0).await.map_err(|e| SourceError::Mpris(e.into()));
+                }
+                PlaybackCommand::Shuffle(shuffle) => {
+                    return proxy.set_property("Shuffle", shuffle).await.map_err(|e| SourceError::Mpris(e.into()));
+                }
+                PlaybackCommand::Repeat(mode) => {
+                    let status = match mode {
+                        RepeatMode::Track => "Track",
+                        RepeatMode::Context => "Playlist",
+                        RepeatMode::Off => "None",
+                    };
+                    return proxy.set_property("LoopStatus", status).await.map_err(|e| SourceError::Mpris(e.into()));
+                }
+                PlaybackCommand::Transfer { .. } | PlaybackCommand::AddToQueue(_) => {
+                    return Err(SourceError::Unavailable("MPRIS players cannot move playback or add to the queue"));
+                }
+            };
+            result.map(|_| ()).map_err(SourceError::Mpris)
+        }
+    }
+
+    fn string(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+        let value = metadata.get(key)?.downcast_ref::<&str>().ok()?;
+        Some(value.to_string())
+    }
+
+    /// `mpris:length` in milliseconds, players disagree on whether it is signed
+    fn length(metadata: &HashMap<String, OwnedValue>) -> Option<u64> {
+        let value = metadata.get("mpris:length")?;
+        let microseconds = value
+            .downcast_ref::<i64>()
+            .map(|length| length.max(0) as u64)
+            .or_else(|_| value.downcast_ref::<u64>())
+            .ok()?;
+        Some(microseconds / 1000)
+    }
+
+    fn strings(metadata: &HashMap<String, OwnedValue>, key: &str) -> Vec<String> {
+        let Some(Ok(array)) = metadata.get(key).map(|value| value.downcast_ref::<&Array>()) else {
+            return Vec::new();
+        };
+        array
+            .iter()
+            .filter_map(|value| value.downcast_ref::<&str>().ok())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Turns the desktop client's `https://open.spotify.com/track/<id>` into `spotify:track:<id>`
+    fn spotify_uri(url: String) -> String {
+        match url.strip_prefix("https://open.spotify.com/track/") {
+            Some(id) => format!("spotify:track:{}", id),
+            None => url,
+        }
+    }
+
+    /// Older desktop clients report artwork on a host that no longer serves it
+    fn art_url(url: String) -> String {
+        url.replace("https://open.spotify.com/image/", "https://i.scdn.co/image/")
+    }
+}
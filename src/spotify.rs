@@ -1,24 +1,84 @@
-use reqwest::{ self, header::{ ACCEPT, CONTENT_TYPE } };
-use serde::{ Serialize, Deserialize };
-use url_search_params;
-use open;
+use reqwest::{ self, header::{ ACCEPT, CONTENT_LENGTH, CONTENT_TYPE }, Method };
+use serde::{ Serialize, Deserialize, de::DeserializeOwned };
 use tokio::{self, select, time::sleep};
 
 use core::panic;
-use std::{time::Duration};
+use std::{fmt, time::Duration};
 use std::collections::HashMap;
 use std::fs;
 
+use crate::playback::{Album, Device, Devices, Image, PlayHistory, PlaybackState, PlayingItem, Queue, RecentlyPlayed, RepeatMode, SavedAlbums};
+use crate::scope::Scope;
+
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 
 const URI: &str = "http://127.0.0.1:8000/callback";
+const API: &str = "https://api.spotify.com/v1";
+
+/// Represents an error returned while talking to the Spotify API
+#[derive(Debug)]
+pub enum SpotifyError {
+    /// The access token is missing or expired
+    Unauthorized,
+    /// Controlling playback needs a Spotify Premium account
+    PremiumRequired,
+    /// The API answered with an unexpected status code
+    Status(reqwest::StatusCode),
+    /// The request could not be sent or the body could not be read
+    Request(reqwest::Error),
+    /// The response body did not match the expected struct
+    Parse(serde_json::Error),
+    /// constants.json or user.json could not be read
+    File(&'static str, std::io::Error),
+}
+
+impl fmt::Display for SpotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpotifyError::Unauthorized => write!(f, "unauthorized"),
+            SpotifyError::PremiumRequired => write!(f, "Spotify Premium is required to control playback"),
+            SpotifyError::Status(status) => write!(f, "unexpected status code: {}", status),
+            SpotifyError::Request(e) => write!(f, "request failed: {}", e),
+            SpotifyError::Parse(e) => write!(f, "could not parse response: {}", e),
+            SpotifyError::File(name, e) => write!(f, "could not read {}: {}", name, e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for SpotifyError {
+    fn from(e: reqwest::Error) -> Self {
+        SpotifyError::Request(e)
+    }
+}
+
+impl From<serde_json::Error> for SpotifyError {
+    fn from(e: serde_json::Error) -> Self {
+        SpotifyError::Parse(e)
+    }
+}
+
+/// Represents the body of an error response from the Web API
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+/// Represents the error inside an error response
+#[derive(Deserialize, Debug)]
+struct ErrorDetail {
+    /// Machine readable cause, only sent by some endpoints, e.g. `PREMIUM_REQUIRED`
+    reason: Option<String>,
+}
 
 /// Represents a refresh token response
 #[derive(Serialize, Deserialize, Debug)]
 struct AuthResponse {
     access_token: String,
     refresh_token: String,
+    /// Scopes the user actually approved
+    #[serde(default)]
+    scope: String,
 }
 
 /// Represents an access token response
@@ -32,12 +92,44 @@ struct Access {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AuthFile {
     refresh: String,
+    /// Scopes granted to the refresh token, files written before this was stored hold the legacy scope
+    #[serde(default = "Scope::legacy")]
+    scope: Scope,
 }
 
 /// Represents a response for user data
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct User {
-    display_name: String
+pub struct UserProfile {
+    /// Spotify user ID
+    pub id: String,
+    /// Account display name, Spotify allows this to be empty
+    pub display_name: Option<String>,
+    /// ISO 3166-1 alpha-2 country code
+    pub country: Option<String>,
+    /// Subscription level of the account
+    pub product: Option<Product>,
+    /// Profile pictures, possibly empty
+    #[serde(default)]
+    pub images: Vec<Image>,
+    pub followers: Option<Followers>,
+}
+
+/// Represents the subscription level of an account
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Product {
+    Premium,
+    Free,
+    /// Older name for the free tier
+    Open,
+    #[serde(other)]
+    Unknown,
+}
+
+/// Represents follower information of a profile
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Followers {
+    pub total: u64,
 }
 
 /// Represents a json file holding client id and secret
@@ -48,19 +140,50 @@ pub struct Constants{
 }
 
 /// Represents a client that can send requests to the Spotify API
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct SpotifyUser {
-    /// Account display name
-    username: String,
+    /// Account profile, loaded after signing in
+    profile: Option<UserProfile>,
+    /// Encoded profile picture
+    avatar: Option<Vec<u8>>,
     /// User account's access token
     token: String,
     /// Spotify app client ID
     id: String,
     /// Spotify app client secret
-    secret: String
+    secret: String,
+    /// Scopes requested when signing in
+    scope: Scope,
+    /// Base URL of the Web API, pointed elsewhere by the tests
+    api: String,
+}
+
+impl Default for SpotifyUser {
+    fn default() -> Self {
+        Self {
+            profile: None,
+            avatar: None,
+            token: String::new(),
+            id: String::new(),
+            secret: String::new(),
+            scope: Scope::default(),
+            api: String::from(API),
+        }
+    }
 }
 
 impl SpotifyUser {
+    /// Creates a client from the client ID and secret stored in constants.json
+    pub fn from_constants() -> Result<SpotifyUser, SpotifyError> {
+        let contents = fs::read_to_string("constants.json").map_err(|e| SpotifyError::File("constants.json", e))?;
+        let constants: Constants = serde_json::from_str(&contents)?;
+        Ok(SpotifyUser {
+            id: constants.id,
+            secret: constants.secret,
+            ..Default::default()
+        })
+    }
+
     /// Sets the client ID
     pub fn set_id(&mut self, id: &String) {
         self.id = id.to_string();
@@ -71,56 +194,71 @@ impl SpotifyUser {
         self.secret = secret.to_string();
     }
 
+    /// Sets the scopes requested when signing in
+    pub fn set_scope(&mut self, scope: Scope) {
+        self.scope = scope;
+    }
+
+    /// Scopes granted to the refresh token in user.json, `None` if there is no stored token
+    pub fn granted_scope() -> Option<Scope> {
+        let contents = fs::read_to_string("user.json").ok()?;
+        let file: AuthFile = serde_json::from_str(&contents).ok()?;
+        Some(file.scope)
+    }
+
+    /// Retrieves the user's profile and avatar, replacing any previously loaded profile
     #[tokio::main]
-    pub async fn set_username(&mut self) {
-        let url = format!("https://api.spotify.com/v1/me");
+    pub async fn load_profile(&mut self) -> Result<(), SpotifyError> {
+        let profile = self.get::<UserProfile>("/me").await?.ok_or(SpotifyError::Status(reqwest::StatusCode::NO_CONTENT))?;
+        self.avatar = match profile.images.first() {
+            Some(image) => download_image(&image.url).await,
+            None => None,
+        };
+        self.profile = Some(profile);
+        Ok(())
+    }
 
-        let client = reqwest::Client::new();
-        let response = client
-            .get(&url)
-            .bearer_auth(&self.token)
-            .header(ACCEPT, "application/json") // Recieve json response
-            .send()
-            .await
-            .unwrap();
+    /// Profile of the signed in account, if it has been loaded
+    pub fn profile(&self) -> Option<&UserProfile> {
+        self.profile.as_ref()
+    }
 
-        match response.status() {
-            reqwest::StatusCode::OK => {
-                let res = response.text().await.unwrap();
-                match serde_json::from_str::<User>(&res) {
-                    Ok(user) => {
-                        self.username = user.display_name
-                    }
-                    Err(_) => {
-                        panic!("Could not generate username")
-                    }
-                }
-            }
-            reqwest::StatusCode::UNAUTHORIZED => {
-                panic!("Unauthorized");
-            }
-            reqwest::StatusCode::NO_CONTENT => {
-                panic!("No content")
-            }
-            _other => {
-                panic!("panicked with status code: {}", response.text().await.unwrap())
-            }
-        }
+    /// Encoded profile picture of the signed in account, if it has one
+    pub fn avatar(&self) -> Option<&[u8]> {
+        self.avatar.as_deref()
     }
 
+    /// Display name of the account, falling back to the user ID when Spotify has none
     pub(crate) fn get_username(&self) -> &str {
-        return &self.username
+        match &self.profile {
+            Some(profile) => profile.display_name.as_deref().unwrap_or(&profile.id),
+            None => "",
+        }
     }
     
     /// Generates access token for account specific data request
     #[tokio::main]
     pub(crate) async fn generate_token(&mut self){
+        match self.refresh_token().await {
+            Ok(()) => {}
+            Err(e @ (SpotifyError::Parse(_) | SpotifyError::File(..))) => {
+                println!("There was an error: {e}");
+            }
+            Err(other) => {
+                panic!("Could not generate token: {}", other)
+            },
+        }
+    }
+
+    /// Requests a new access token using the refresh token in user.json
+    ///
+    /// Unlike [`SpotifyUser::generate_token`], this can be awaited from inside a running runtime
+    pub(crate) async fn refresh_token(&mut self) -> Result<(), SpotifyError> {
         // Convert user.json to struct format
-        let file: AuthFile = serde_json
-            ::from_str(fs::read_to_string("user.json").expect("Error opening file").as_str())
-            .expect("Could not convert to json");
+        let contents = fs::read_to_string("user.json").map_err(|e| SpotifyError::File("user.json", e))?;
+        let file: AuthFile = serde_json::from_str(&contents)?;
         // Retrieves refresh token
-        let refresh = String::from(file.refresh);
+        let refresh = file.refresh;
         let auth_url = "https://accounts.spotify.com/api/token";
 
         let client = reqwest::Client::new();
@@ -136,26 +274,193 @@ impl SpotifyUser {
             .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
             .basic_auth(&self.id, Some(&self.secret)) // Authorize based on client ID and secret
             .body(url_search_params::build_url_search_params(params))
-            .send().await
-            .unwrap();
+            .send().await?;
 
         // Listen for response from API
         match response.status() {
             reqwest::StatusCode::OK => {
                 // Convert response json to struct
-                match response.json::<Access>().await {
+                match serde_json::from_str::<Access>(&response.text().await?) {
                     Ok(parsed) => {
                         self.token = parsed.access_token; // Recieve access token
+                        Ok(())
                     }
                     Err(e) => {
-                        println!("There was an error: {e}");
                         self.token = String::new(); // Set token to empty to avoid using a problematic token
+                        Err(SpotifyError::Parse(e))
                     }, // Likely invalid authorization code
                 }
             }
-            other => {
-                panic!("Could not generate token: {:?}", other)
-            },
+            other => Err(SpotifyError::Status(other)),
+        }
+    }
+
+    /// Sends an authorized GET request to a Web API endpoint and parses the json response
+    ///
+    /// Returns `None` when the API answers with no content
+    async fn get<T: DeserializeOwned>(&self, endpoint: &str) -> Result<Option<T>, SpotifyError> {
+        let client = reqwest::Client::new();
+        let response = client
+            .get(format!("{}{}", self.api, endpoint))
+            .bearer_auth(&self.token)
+            .header(ACCEPT, "application/json") // Recieve json response
+            .send()
+            .await?;
+
+        match response.status() {
+            reqwest::StatusCode::OK => {
+                let res = response.text().await?;
+                Ok(Some(serde_json::from_str::<T>(&res)?))
+            }
+            reqwest::StatusCode::NO_CONTENT => Ok(None),
+            reqwest::StatusCode::UNAUTHORIZED => Err(SpotifyError::Unauthorized),
+            other => Err(SpotifyError::Status(other)),
+        }
+    }
+
+    /// Sends an authorized request to a Web API endpoint that changes playback
+    ///
+    /// `device` targets a device by ID instead of the active one, and `body` is sent as json if given
+    async fn send(
+        &self,
+        method: Method,
+        endpoint: &str,
+        mut query: Vec<(&str, String)>,
+        device: Option<&str>,
+        body: Option<serde_json::Value>,
+    ) -> Result<(), SpotifyError> {
+        if let Some(device) = device {
+            query.push(("device_id", device.to_string()));
+        }
+        let client = reqwest::Client::new();
+        let request = client
+            .request(method, format!("{}{}", self.api, endpoint))
+            .bearer_auth(&self.token)
+            .query(&query);
+        let request = match body {
+            Some(body) => request.json(&body),
+            None => request.header(CONTENT_LENGTH, 0), // Spotify rejects bodiless PUT requests without a length
+        };
+        let response = request.send().await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            reqwest::StatusCode::UNAUTHORIZED => Err(SpotifyError::Unauthorized),
+            reqwest::StatusCode::FORBIDDEN => {
+                // Free accounts are refused with a reason, other refusals are left as the status
+                let res = response.text().await?;
+                match serde_json::from_str::<ErrorResponse>(&res) {
+                    Ok(error) if error.error.reason.as_deref() == Some("PREMIUM_REQUIRED") => Err(SpotifyError::PremiumRequired),
+                    _ => Err(SpotifyError::Status(reqwest::StatusCode::FORBIDDEN)),
+                }
+            }
+            other => Err(SpotifyError::Status(other)),
+        }
+    }
+
+    /// Resumes playback on `device`, or the active device if `None`
+    pub async fn play(&self, device: Option<&str>) -> Result<(), SpotifyError> {
+        self.send(Method::PUT, "/me/player/play", Vec::new(), device, None).await
+    }
+
+    /// Pauses playback on `device`, or the active device if `None`
+    pub async fn pause(&self, device: Option<&str>) -> Result<(), SpotifyError> {
+        self.send(Method::PUT, "/me/player/pause", Vec::new(), device, None).await
+    }
+
+    /// Skips to the next item in the queue
+    pub async fn next(&self, device: Option<&str>) -> Result<(), SpotifyError> {
+        self.send(Method::POST, "/me/player/next", Vec::new(), device, None).await
+    }
+
+    /// Skips to the previous item
+    pub async fn previous(&self, device: Option<&str>) -> Result<(), SpotifyError> {
+        self.send(Method::POST, "/me/player/previous", Vec::new(), device, None).await
+    }
+
+    /// Jumps to `position_ms` in the playing item
+    pub async fn seek(&self, position_ms: u64, device: Option<&str>) -> Result<(), SpotifyError> {
+        let query = vec![("position_ms", position_ms.to_string())];
+        self.send(Method::PUT, "/me/player/seek", query, device, None).await
+    }
+
+    /// Sets the volume to `percent`, capped at 100
+    pub async fn set_volume(&self, percent: u8, device: Option<&str>) -> Result<(), SpotifyError> {
+        let query = vec![("volume_percent", percent.min(100).to_string())];
+        self.send(Method::PUT, "/me/player/volume", query, device, None).await
+    }
+
+    /// Turns shuffle on or off
+    pub async fn set_shuffle(&self, shuffle: bool, device: Option<&str>) -> Result<(), SpotifyError> {
+        let query = vec![("state", shuffle.to_string())];
+        self.send(Method::PUT, "/me/player/shuffle", query, device, None).await
+    }
+
+    /// Sets what repeats once the playing item ends
+    pub async fn set_repeat(&self, mode: RepeatMode, device: Option<&str>) -> Result<(), SpotifyError> {
+        let query = vec![("state", mode.as_str().to_string())];
+        self.send(Method::PUT, "/me/player/repeat", query, device, None).await
+    }
+
+    /// Moves playback to the device with ID `device`, starting it if `play` is set and keeping the current state otherwise
+    pub async fn transfer_playback(&self, device: &str, play: bool) -> Result<(), SpotifyError> {
+        let body = serde_json::json!({ "device_ids": [device], "play": play });
+        self.send(Method::PUT, "/me/player", Vec::new(), None, Some(body)).await
+    }
+
+    /// Adds the track or episode with `uri` to the end of the queue
+    pub async fn add_to_queue(&self, uri: &str, device: Option<&str>) -> Result<(), SpotifyError> {
+        let query = vec![("uri", uri.to_string())];
+        self.send(Method::POST, "/me/player/queue", query, device, None).await
+    }
+
+    /// Retrieves the user's current playback state, including episodes
+    ///
+    /// Returns `None` when nothing is playing on any device
+    pub async fn current_playback(&self) -> Result<Option<PlaybackState>, SpotifyError> {
+        self.get::<PlaybackState>("/me/player?additional_types=episode").await
+    }
+
+    /// Retrieves the devices the user can play on, including the active one
+    pub async fn devices(&self) -> Result<Vec<Device>, SpotifyError> {
+        match self.get::<Devices>("/me/player/devices").await? {
+            Some(devices) => Ok(devices.devices),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Retrieves up to `limit` of the user's most recently played tracks, newest first
+    pub async fn recently_played(&self, limit: u8) -> Result<Vec<PlayHistory>, SpotifyError> {
+        let endpoint = format!("/me/player/recently-played?limit={}", limit.clamp(1, 50));
+        match self.get::<RecentlyPlayed>(&endpoint).await? {
+            Some(recent) => Ok(recent.items),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Retrieves the items that will play after the current one, in order
+    pub async fn queue(&self) -> Result<Vec<PlayingItem>, SpotifyError> {
+        match self.get::<Queue>("/me/player/queue").await? {
+            Some(queue) => Ok(queue.queue),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Retrieves the playing item along with its artwork, for showing it outside the screensaver
+    #[tokio::main]
+    pub async fn now_playing(&self, side: u32) -> Option<(PlayingItem, Vec<u8>)> {
+        let item = self.current_playback().await.ok()??.drawable_item()?.clone();
+        let image = Image::best_fit(item.images(), side)?;
+        let bytes = download_image(&image.url).await?;
+        Some((item, bytes))
+    }
+
+    /// Retrieves up to `limit` of the albums in the user's library, most recently saved first
+    pub async fn saved_albums(&self, limit: u8) -> Result<Vec<Album>, SpotifyError> {
+        let endpoint = format!("/me/albums?limit={}", limit.clamp(1, 50));
+        match self.get::<SavedAlbums>(&endpoint).await? {
+            Some(saved) => Ok(saved.items.into_iter().map(|saved| saved.album).collect()),
+            None => Ok(Vec::new()),
         }
     }
 
@@ -164,13 +469,11 @@ impl SpotifyUser {
         // Open listener at callback URI
         let listener =  tokio::net::TcpListener::bind("127.0.0.1:8000").await.expect("Could not bind");
         
-        // Scope of user data that application can access
-        let scope = "user-read-private user-read-email user-read-playback-state";
         // Parameters for body of URL link
         let params = HashMap::from([
             (String::from("response_type"), String::from("code")),
             (String::from("client_id"), self.id.to_string()),
-            (String::from("scope"), String::from(scope)),
+            (String::from("scope"), self.scope.to_string()), // Scope of user data that application can access
             (String::from("redirect_uri"), String::from(URI)),
         ]);
 
@@ -191,7 +494,7 @@ impl SpotifyUser {
                         Ok((mut stream, _addr)) => {
                             let mut buffer = [0; 512];
                             // Put response into buffer
-                            stream.read(&mut buffer).await.unwrap();
+                            let _ = stream.read(&mut buffer).await.unwrap();
                             // Check if buffer is correcyl formatted with authorization response
                             if buffer.starts_with(b"GET /callback?code="){
                                 // Exctract code from buffer
@@ -219,13 +522,13 @@ impl SpotifyUser {
                 // Times out after 20s
                 _ = sleep(Duration::from_secs(20)) => {
                     println!("timed out");
-                    return String::from("408");
+                    String::from("408")
                 }
             }
         });
         
         // Returns access token
-        return handle.await.unwrap();
+        handle.await.unwrap()
     }
 
     /// Generates refresh token, needed in order to generate access token
@@ -264,8 +567,11 @@ impl SpotifyUser {
                         let refresh_token = parsed.refresh_token;
 
                         // Create json file with refresh token
+                        // Older responses may omit the scope, in which case everything requested was granted
+                        let scope = if parsed.scope.is_empty() { self.scope } else { Scope::parse(&parsed.scope) };
                         let file: AuthFile = AuthFile {
-                            refresh: String::from(refresh_token),
+                            refresh: refresh_token,
+                            scope,
                         };
                         fs::write(
                             "user.json",
@@ -291,4 +597,122 @@ impl SpotifyUser {
         }
     }
 
-}
\ No newline at end of file
+}
+
+impl Product {
+    /// Readable name of the subscription level
+    pub fn label(&self) -> &'static str {
+        match self {
+            Product::Premium => "Premium",
+            Product::Free | Product::Open => "Free",
+            Product::Unknown => "Unknown",
+        }
+    }
+
+    /// Free accounts cannot use playback control endpoints
+    pub fn is_free(&self) -> bool {
+        matches!(self, Product::Free | Product::Open)
+    }
+}
+
+/// Downloads an image, returning `None` if the request fails
+pub async fn download_image(url: &str) -> Option<Vec<u8>> {
+    let response = reqwest::get(url).await.ok()?;
+    let bytes = response.bytes().await.ok()?;
+    Some(bytes.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Answers one request on a local port with `status` and `body`, handing back the request as received
+    async fn answer_once(status: &'static str, body: &'static str) -> (SpotifyUser, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("Could not bind");
+        let address = listener.local_addr().expect("Has an address");
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("Could not accept");
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+            // Reads the headers, then as much body as they announce
+            loop {
+                let read = stream.read(&mut buffer).await.expect("Could not read");
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text
+                        .lines()
+                        .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|value| value.trim().to_string()))
+                        .and_then(|value| value.parse::<usize>().ok())
+                        .unwrap_or(0);
+                    if read == 0 || request.len() >= end + 4 + length {
+                        break;
+                    }
+                } else if read == 0 {
+                    break;
+                }
+            }
+            let response = format!("HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len());
+            stream.write_all(response.as_bytes()).await.expect("Could not answer");
+            String::from_utf8_lossy(&request).to_string()
+        });
+        let user = SpotifyUser { api: format!("http://{address}"), token: String::from("token"), ..Default::default() };
+        (user, server)
+    }
+
+    #[tokio::test]
+    async fn control_statuses_map_to_errors() {
+        let cases: [(&str, &str, &str); 5] = [
+            ("204 No Content", "", "ok"),
+            ("200 OK", "", "ok"),
+            ("403 Forbidden", r#"{"error":{"status":403,"message":"Player command failed","reason":"PREMIUM_REQUIRED"}}"#, "premium"),
+            ("403 Forbidden", r#"{"error":{"status":403,"message":"Player command failed","reason":"UNKNOWN"}}"#, "403"),
+            ("401 Unauthorized", r#"{"error":{"status":401,"message":"The access token expired"}}"#, "unauthorized"),
+        ];
+        for (status, body, expected) in cases {
+            let (user, server) = answer_once(status, body).await;
+            let outcome = match user.play(None).await {
+                Ok(()) => "ok",
+                Err(SpotifyError::PremiumRequired) => "premium",
+                Err(SpotifyError::Status(reqwest::StatusCode::FORBIDDEN)) => "403",
+                Err(SpotifyError::Unauthorized) => "unauthorized",
+                Err(e) => std::panic!("{status} gave {e}"),
+            };
+            assert_eq!(outcome, expected, "{status}");
+            let request = server.await.expect("Server panicked");
+            assert!(request.starts_with("PUT /me/player/play HTTP/1.1"), "{request}");
+            assert!(request.to_ascii_lowercase().contains("authorization: bearer token"), "{request}");
+        }
+    }
+
+    #[tokio::test]
+    async fn device_and_values_go_in_the_query() {
+        let (user, server) = answer_once("204 No Content", "").await;
+        user.seek(25_000, Some("desk")).await.expect("Could not seek");
+        let request = server.await.expect("Server panicked");
+        assert!(request.starts_with("PUT /me/player/seek?position_ms=25000&device_id=desk HTTP/1.1"), "{request}");
+
+        let (user, server) = answer_once("204 No Content", "").await;
+        user.set_volume(150, Some("desk")).await.expect("Could not set volume");
+        let request = server.await.expect("Server panicked");
+        assert!(request.starts_with("PUT /me/player/volume?volume_percent=100&device_id=desk HTTP/1.1"), "{request}");
+
+        let (user, server) = answer_once("204 No Content", "").await;
+        user.next(None).await.expect("Could not skip");
+        let request = server.await.expect("Server panicked");
+        assert!(request.starts_with("POST /me/player/next HTTP/1.1"), "{request}");
+        assert!(request.to_ascii_lowercase().contains("content-length: 0"), "{request}");
+    }
+
+    #[tokio::test]
+    async fn transfer_sends_the_device_in_the_body() {
+        let (user, server) = answer_once("204 No Content", "").await;
+        user.transfer_playback("desk", true).await.expect("Could not transfer");
+        let request = server.await.expect("Server panicked");
+        assert!(request.starts_with("PUT /me/player HTTP/1.1"), "{request}");
+        let (_, body) = request.split_once("\r\n\r\n").expect("Has a body");
+        let body: serde_json::Value = serde_json::from_str(body).expect("Body is json");
+        assert_eq!(body, serde_json::json!({ "device_ids": ["desk"], "play": true }));
+    }
+}
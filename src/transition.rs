@@ -0,0 +1,102 @@
+use iced::widget::{canvas, image};
+use iced::{Point, Rectangle, Size};
+
+use std::time::Instant;
+
+use crate::bounce::SplitMix;
+use crate::settings::{TransitionSettings, TransitionStyle};
+
+/// Rows and columns the outgoing cover is cut into for the dissolve
+const DISSOLVE_CELLS: u32 = 10;
+
+/// Represents the cover on screen turning into the next one
+pub struct CoverTransition {
+    from: image::Handle,
+    /// Pieces of `from` for the dissolve, row by row, with the progress at which each disappears
+    tiles: Vec<(image::Handle, f32)>,
+    started: Instant,
+}
+
+impl CoverTransition {
+    /// Starts turning `from` into whatever is drawn next
+    ///
+    /// `from` should be decoded already, so the dissolve can cut it up right away
+    pub fn new(settings: &TransitionSettings, from: image::Handle, now: Instant) -> CoverTransition {
+        let tiles = match settings.style {
+            TransitionStyle::Dissolve => tiles(&from),
+            _ => Vec::new(),
+        };
+        CoverTransition { from, tiles, started: now }
+    }
+
+    /// Eased progress from 0 to 1, `None` once the transition is over
+    pub fn progress(&self, settings: &TransitionSettings, now: Instant) -> Option<f32> {
+        let linear = now.duration_since(self.started).as_secs_f32() / (settings.duration_ms.max(1) as f32 / 1000.0);
+        (linear < 1.0).then(|| settings.easing.apply(linear))
+    }
+
+    /// Draws the transition `progress` of the way from the previous cover to `to`
+    pub fn draw(&self, frame: &mut canvas::Frame, style: TransitionStyle, square: Rectangle, to: &image::Handle, progress: f32) {
+        match style {
+            TransitionStyle::None => frame.draw_image(square, to),
+            TransitionStyle::Crossfade => {
+                frame.draw_image(square, &self.from);
+                frame.draw_image(square, canvas::Image::new(to.clone()).opacity(progress));
+            }
+            // Squeezing the width to nothing and back reads as the cover turning over
+            TransitionStyle::Flip => {
+                let (handle, width) = if progress < 0.5 {
+                    (&self.from, square.width * (1.0 - 2.0 * progress))
+                } else {
+                    (to, square.width * (2.0 * progress - 1.0))
+                };
+                let bounds = Rectangle::new(
+                    Point::new(square.center_x() - width / 2.0, square.y),
+                    Size::new(width, square.height),
+                );
+                frame.draw_image(bounds, handle);
+            }
+            // Images are not clipped to frames, so both covers fade as they leave and enter the square
+            TransitionStyle::Slide => {
+                let outgoing = Rectangle { x: square.x - square.width * progress, ..square };
+                let incoming = Rectangle { x: square.x + square.width * (1.0 - progress), ..square };
+                frame.draw_image(outgoing, canvas::Image::new(self.from.clone()).opacity(1.0 - progress));
+                frame.draw_image(incoming, canvas::Image::new(to.clone()).opacity(progress));
+            }
+            TransitionStyle::Dissolve => {
+                frame.draw_image(square, to);
+                let cell = Size::new(square.width / DISSOLVE_CELLS as f32, square.height / DISSOLVE_CELLS as f32);
+                for (index, (tile, vanishes_at)) in self.tiles.iter().enumerate() {
+                    if progress >= *vanishes_at {
+                        continue;
+                    }
+                    let (row, column) = (index as u32 / DISSOLVE_CELLS, index as u32 % DISSOLVE_CELLS);
+                    let position = Point::new(square.x + column as f32 * cell.width, square.y + row as f32 * cell.height);
+                    frame.draw_image(Rectangle::new(position, cell), tile);
+                }
+            }
+        }
+    }
+}
+
+/// Cuts a decoded image into [`DISSOLVE_CELLS`] squared tiles, each vanishing at a random point of the transition
+fn tiles(handle: &image::Handle) -> Vec<(image::Handle, f32)> {
+    let image::Handle::Rgba { width, height, pixels, .. } = handle else {
+        return Vec::new();
+    };
+    let mut random = SplitMix(pixels.len() as u64);
+    let mut tiles = Vec::new();
+    for row in 0..DISSOLVE_CELLS {
+        for column in 0..DISSOLVE_CELLS {
+            let (left, right) = (column * width / DISSOLVE_CELLS, (column + 1) * width / DISSOLVE_CELLS);
+            let (top, bottom) = (row * height / DISSOLVE_CELLS, (row + 1) * height / DISSOLVE_CELLS);
+            let mut tile = Vec::with_capacity(((right - left) * (bottom - top) * 4) as usize);
+            for y in top..bottom {
+                let start = ((y * width + left) * 4) as usize;
+                tile.extend_from_slice(&pixels[start..start + ((right - left) * 4) as usize]);
+            }
+            tiles.push((image::Handle::from_rgba(right - left, bottom - top, tile), random.next_f32()));
+        }
+    }
+    tiles
+}